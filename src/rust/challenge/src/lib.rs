@@ -0,0 +1,406 @@
+//! Detection and solving of the anti-bot pages some sites serve instead of
+//! the requested page.
+//!
+//! Requests made through [`ChallengeRequest`] are checked for known
+//! challenges. Cookie and redirect challenges are solved and the resulting
+//! cookies are remembered per host, while the ones that need a browser are
+//! reported as [`ChallengeError::NeedsWebVerification`].
+#![no_std]
+use aidoku::{
+	error::{AidokuError, AidokuErrorKind},
+	helpers::substring::Substring,
+	prelude::{format, println},
+	std::{
+		html::Node,
+		net::{HttpMethod, Request},
+		String, Vec,
+	},
+};
+use core::{fmt, ptr};
+
+/// Number of challenges solved for a single request before giving up.
+const MAX_STEPS: usize = 5;
+
+/// Status codes challenge pages are served with.
+const CHALLENGE_STATUS_CODES: [i32; 3] = [403, 429, 503];
+
+/// Titles of challenge pages.
+const CHALLENGE_TITLES: [&str; 4] = [
+	"Just a moment",
+	"DDoS-Guard",
+	"Attention Required",
+	"Checking your browser",
+];
+
+/// Length of the text of a page below which it's considered empty, as
+/// challenge pages only consist of their scripts.
+const MAX_CHALLENGE_TEXT_LEN: usize = 256;
+
+#[derive(Debug)]
+pub enum ChallengeError {
+	/// The page is protected by a challenge that has to be completed in a
+	/// web view, e.g. Cloudflare or DDoS-Guard.
+	NeedsWebVerification(String),
+	/// Solving the challenges kept leading to new ones.
+	TooManyChallenges(String),
+	Request(AidokuError),
+}
+
+impl fmt::Display for ChallengeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NeedsWebVerification(url) => {
+				write!(f, "{} needs to be verified in a web view", url)
+			}
+			Self::TooManyChallenges(url) => write!(f, "{} kept serving challenges", url),
+			Self::Request(error) => write!(f, "request failed: {:?}", error.reason),
+		}
+	}
+}
+
+impl From<AidokuError> for ChallengeError {
+	fn from(error: AidokuError) -> Self {
+		Self::Request(error)
+	}
+}
+
+impl ChallengeError {
+	/// Converts the error for returning it to the app. Errors can't carry a
+	/// message, so the challenge the request is stuck at is written to the
+	/// app's logs first.
+	pub fn report(self) -> AidokuError {
+		match self {
+			Self::Request(error) => error,
+			Self::NeedsWebVerification(_) | Self::TooManyChallenges(_) => {
+				println!("{}", self);
+				AidokuError {
+					reason: AidokuErrorKind::Unimplemented, // no better error type available
+				}
+			}
+		}
+	}
+}
+
+pub type Result<T> = core::result::Result<T, ChallengeError>;
+
+/// A challenge found in a page.
+#[derive(Debug, PartialEq)]
+pub enum Challenge {
+	/// A script setting a cookie before reloading the page (vinahost).
+	Cookie(String),
+	/// A script submitting a form (TuMangaOnline's `uniqid`).
+	FormPost { action: String, body: String },
+	/// A script or hidden input sending the browser to another page.
+	Redirect(String),
+	/// A challenge that can only be completed in a browser.
+	Unsolvable,
+}
+
+/// Cookies obtained by solving challenges, as (host, name=value) pairs.
+static mut COOKIES: Vec<(String, String)> = Vec::new();
+
+fn cookies() -> &'static mut Vec<(String, String)> {
+	unsafe { &mut *ptr::addr_of_mut!(COOKIES) }
+}
+
+fn get_host(url: &str) -> &str {
+	url.split('/').nth(2).unwrap_or_default()
+}
+
+fn store_cookie(url: &str, cookie: &str) {
+	let host = get_host(url);
+	let name = cookie.substring_before('=').unwrap_or(cookie);
+	let cookies = cookies();
+	cookies.retain(|(cookie_host, pair)| {
+		cookie_host != host || pair.substring_before('=').unwrap_or(pair) != name
+	});
+	cookies.push((String::from(host), String::from(cookie)));
+}
+
+/// Returns the `Cookie` header value for the challenge cookies of `url`'s
+/// host, if there are any.
+pub fn get_cookie_header(url: &str) -> Option<String> {
+	let host = get_host(url);
+	let pairs = cookies()
+		.iter()
+		.filter(|(cookie_host, _)| cookie_host == host)
+		.map(|(_, pair)| pair.as_str())
+		.collect::<Vec<_>>();
+	if pairs.is_empty() {
+		None
+	} else {
+		Some(pairs.join("; "))
+	}
+}
+
+/// Adds the challenge cookies of `url`'s host to a request made outside of
+/// [`ChallengeRequest`], e.g. a POST to an ajax endpoint.
+pub fn with_cookies(request: Request, url: &str) -> Request {
+	match get_cookie_header(url) {
+		Some(cookie) => request.header("Cookie", cookie.as_str()),
+		None => request,
+	}
+}
+
+/// Reads a quoted javascript string at the start of `text`, returning it
+/// and what follows it.
+fn read_js_string(text: &str) -> Option<(String, &str)> {
+	let text = text.trim_start();
+	let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+	let mut value = String::new();
+	let mut escaped = false;
+	for (at, char) in text[1..].char_indices() {
+		if escaped {
+			value.push(char);
+			escaped = false;
+		} else if char == '\\' {
+			escaped = true;
+		} else if char == quote {
+			return Some((value, &text[at + 2..]));
+		} else {
+			value.push(char);
+		}
+	}
+	None
+}
+
+/// Reads a concatenation of javascript strings, like `"a" + "b"`.
+fn read_js_concat(text: &str) -> Option<String> {
+	let (mut value, mut rest) = read_js_string(text)?;
+	while let Some(next) = rest.trim_start().strip_prefix('+') {
+		let (part, after) = read_js_string(next)?;
+		value.push_str(&part);
+		rest = after;
+	}
+	Some(value)
+}
+
+/// Reads the string assigned to `name` in a script.
+fn find_assignment(script: &str, name: &str) -> Option<String> {
+	script.match_indices(name).find_map(|(at, _)| {
+		let value = script[at + name.len()..].trim_start().strip_prefix('=')?;
+		if value.starts_with('=') {
+			return None;
+		}
+		read_js_concat(value)
+	})
+}
+
+/// Reads the string passed to the first call of `function` in a script.
+fn find_call_argument(script: &str, function: &str) -> Option<String> {
+	let call = format!("{}(", function);
+	script
+		.match_indices(&call)
+		.find_map(|(at, _)| read_js_concat(&script[at + call.len()..]))
+}
+
+/// Reads the value of `key` in a javascript object literal.
+fn find_property(script: &str, key: &str) -> Option<String> {
+	let value = script
+		.substring_after(key)?
+		.trim_start()
+		.strip_prefix(':')?;
+	read_js_string(value).map(|(value, _)| value).or_else(|| {
+		let end = value.find([',', '}']).unwrap_or(value.len());
+		Some(String::from(value[..end].trim()))
+	})
+}
+
+/// Returns the absolute version of `target`, which was found on `url`.
+fn resolve_url(url: &str, target: &str) -> String {
+	if target.starts_with("http") {
+		String::from(target)
+	} else if target.starts_with("//") {
+		format!(
+			"{}:{}",
+			url.substring_before(':').unwrap_or("https"),
+			target
+		)
+	} else {
+		let origin = url.split('/').take(3).collect::<Vec<_>>().join("/");
+		format!("{}/{}", origin, target.trim_start_matches('/'))
+	}
+}
+
+/// Looks for a known challenge in a page served with `status_code`.
+///
+/// Unless `follow_script_redirects` is set, pages are only checked for
+/// solvable challenges when they look like one, i.e. they were served with a
+/// challenge status code, have a challenge title or no text besides their
+/// scripts, as regular pages may contain the same scripts. Script redirects
+/// are only reported when `follow_script_redirects` is set, since sites use
+/// them on regular pages too.
+pub fn detect(
+	html: &Node,
+	url: &str,
+	status_code: i32,
+	follow_script_redirects: bool,
+) -> Option<Challenge> {
+	let script = html.select("script").html().read();
+	let title = html.select("title").text().read();
+
+	if title.starts_with("Just a moment")
+		|| title.contains("DDoS-Guard")
+		|| script.contains("cf_chl_opt")
+		|| !html
+			.select("#challenge-form, #challenge-running")
+			.array()
+			.is_empty()
+	{
+		return Some(Challenge::Unsolvable);
+	}
+
+	let is_challenge_page = CHALLENGE_STATUS_CODES.contains(&status_code)
+		|| CHALLENGE_TITLES.iter().any(|v| title.contains(v))
+		|| html.select("body").text().read().trim().len() < MAX_CHALLENGE_TEXT_LEN;
+	if !follow_script_redirects && !is_challenge_page {
+		return None;
+	}
+
+	if script.contains("document.cookie") && script.contains("location.reload") {
+		if let Some(cookie) = find_assignment(&script, "document.cookie") {
+			let cookie = cookie.substring_before(';').unwrap_or(&cookie).trim();
+			if cookie.contains('=') {
+				return Some(Challenge::Cookie(String::from(cookie)));
+			}
+		}
+	}
+
+	if script.contains("uniqid") {
+		if let (Some(uniqid), Some(cascade), Some(action)) = (
+			find_property(&script, "uniqid"),
+			find_property(&script, "cascade"),
+			find_assignment(&script, "form.action"),
+		) {
+			return Some(Challenge::FormPost {
+				action: resolve_url(url, &action),
+				body: format!("uniqid={}&cascade={}", uniqid, cascade),
+			});
+		}
+	}
+
+	if !follow_script_redirects {
+		return None;
+	}
+
+	let target = find_call_argument(&script, "window.location.replace")
+		.or_else(|| find_assignment(&script, "redirectUrl"))
+		.or_else(|| {
+			let input = html.select("input#redir");
+			let value = input.attr("value").read();
+			let value = if value.is_empty() {
+				input.text().read()
+			} else {
+				value
+			};
+			if value.is_empty() {
+				None
+			} else {
+				Some(value)
+			}
+		})
+		.or_else(|| {
+			if script.contains("window.opener") {
+				find_call_argument(&script, "location.replace")
+			} else {
+				None
+			}
+		})?;
+	Some(Challenge::Redirect(resolve_url(url, &target)))
+}
+
+/// A GET request that solves the challenges served instead of the page.
+pub struct ChallengeRequest<'a> {
+	url: String,
+	headers: Vec<(&'a str, &'a str)>,
+	follow_script_redirects: bool,
+}
+
+impl<'a> ChallengeRequest<'a> {
+	pub fn get<T: AsRef<str>>(url: T) -> Self {
+		Self {
+			url: String::from(url.as_ref()),
+			headers: Vec::new(),
+			follow_script_redirects: false,
+		}
+	}
+
+	pub fn header(mut self, key: &'a str, value: &'a str) -> Self {
+		self.headers.push((key, value));
+		self
+	}
+
+	/// Also follow `location.replace`, `redirectUrl` and `input#redir`
+	/// redirects.
+	pub fn follow_script_redirects(mut self) -> Self {
+		self.follow_script_redirects = true;
+		self
+	}
+
+	fn build(&self, url: &str, method: HttpMethod, referer: Option<&str>) -> Request {
+		let mut request = Request::new(url, method);
+		for (key, value) in &self.headers {
+			if referer.is_some() && key.eq_ignore_ascii_case("Referer") {
+				continue;
+			}
+			request = request.header(key, value);
+		}
+		if let Some(referer) = referer {
+			request = request.header("Referer", referer);
+		}
+		with_cookies(request, url)
+	}
+
+	/// Returns the response body and its document once all challenges are
+	/// solved.
+	fn send(self) -> Result<(Vec<u8>, Node)> {
+		let mut url = self.url.clone();
+		let mut request = self.build(&url, HttpMethod::Get, None);
+		// the response the last cookie was set on
+		let mut cookie_response: Option<Vec<u8>> = None;
+
+		for _ in 0..MAX_STEPS {
+			request.send();
+			let status_code = request.status_code();
+			let data = request.data();
+			let html = Node::new_with_uri(&data, &url)?;
+			let previous = url.clone();
+			request = match detect(&html, &url, status_code, self.follow_script_redirects) {
+				None => return Ok((data, html)),
+				Some(Challenge::Unsolvable) => {
+					return Err(ChallengeError::NeedsWebVerification(url))
+				}
+				Some(Challenge::Cookie(cookie)) => {
+					// the page doesn't depend on the cookie when setting it
+					// didn't change anything, so it isn't a challenge
+					if cookie_response.as_ref() == Some(&data) {
+						return Ok((data, html));
+					}
+					store_cookie(&url, &cookie);
+					cookie_response = Some(data);
+					self.build(&url, HttpMethod::Get, None)
+				}
+				Some(Challenge::FormPost { action, body }) => {
+					url = action;
+					self.build(&url, HttpMethod::Post, Some(&previous))
+						.header("Content-Type", "application/x-www-form-urlencoded")
+						.body(body.as_bytes())
+				}
+				Some(Challenge::Redirect(target)) => {
+					url = target;
+					self.build(&url, HttpMethod::Get, Some(&previous))
+				}
+			};
+		}
+
+		Err(ChallengeError::TooManyChallenges(url))
+	}
+
+	pub fn data(self) -> Result<Vec<u8>> {
+		self.send().map(|(data, _)| data)
+	}
+
+	pub fn html(self) -> Result<Node> {
+		self.send().map(|(_, html)| html)
+	}
+}
@@ -3,12 +3,12 @@ use aidoku::{
 	error::Result,
 	helpers::substring::Substring,
 	prelude::*,
-	std::{defaults::defaults_get, html::Node, net::HttpMethod, net::Request, String, Vec},
+	std::{defaults::defaults_get, html::Node, net::Request, String, Vec},
 	Chapter, Filter, FilterType, Listing, Manga, MangaContentRating, MangaPageResult, MangaStatus,
 	MangaViewer, Page,
 };
 
-use regex::Regex;
+use challenge::{ChallengeError, ChallengeRequest};
 
 extern crate alloc;
 use alloc::{borrow::ToOwned, string::ToString};
@@ -192,10 +192,11 @@ fn get_manga_details(id: String) -> Result<Manga> {
 	} else {
 		format!("{BASE_URL}{id}")
 	};
-	let html = Request::new(&url, HttpMethod::Get)
+	let html = ChallengeRequest::get(&url)
 		.header("User-Agent", USER_AGENT)
 		.header("Referer", BASE_URL)
-		.html()?;
+		.html()
+		.map_err(ChallengeError::report)?;
 
 	let cover = html.select(".book-thumbnail").attr("src").read();
 	let title = html.select("h1.element-title").first().own_text().read();
@@ -280,78 +281,6 @@ fn parse_chapter(element: Node) -> Chapter {
 	}
 }
 
-fn get_redirect_read_page(html: Node) -> Result<Node> {
-	let url = html.base_uri().to_string();
-	let script = html.select("script").to_string();
-
-	if script.contains("uniqid") {
-		let regex_params =
-			Regex::new(r"\{\s*uniqid\s*:\s*'(.+)'\s*,\s*cascade\s*:\s*(.+)\s*\}").unwrap();
-		let regex_action = Regex::new(r"form\.action\s*=\s*'(.+)'").unwrap();
-
-		if let (Some(params), Some(action)) = (
-			regex_params.captures(&script),
-			regex_action.captures(&script),
-		) {
-			let parameters = format!("uniqid={}&cascade={}", &params[1], &params[2]);
-			let request = Request::new(&action[1], HttpMethod::Post)
-				.header("User-Agent", USER_AGENT)
-				.header("Referer", &url)
-				.body(parameters)
-				.html()?;
-
-			return get_redirect_read_page(request);
-		}
-	}
-
-	if script.contains("window.location.replace") {
-		let regex_redirect = Regex::new(r#"window\.location\.replace\(['"](.+)['"]\)"#).unwrap();
-
-		if let Some(url) = regex_redirect.captures(&script) {
-			let request = Request::new(&url[1], HttpMethod::Get)
-				.header("User-Agent", USER_AGENT)
-				.html()?;
-
-			return get_redirect_read_page(request);
-		}
-	}
-
-	if script.contains("redirectUrl") {
-		let regex_redirect = Regex::new(r"redirectUrl\s*=\s*'(.+)'").unwrap();
-
-		if let Some(url) = regex_redirect.captures(&script) {
-			let request = Request::new(&url[1], HttpMethod::Get)
-				.header("User-Agent", USER_AGENT)
-				.html()?;
-
-			return get_redirect_read_page(request);
-		}
-	}
-
-	let input_redir = html.select("input#redir").text().read();
-	if !input_redir.is_empty() {
-		let request = Request::new(&input_redir, HttpMethod::Get)
-			.header("User-Agent", USER_AGENT)
-			.html()?;
-
-		return get_redirect_read_page(request);
-	}
-
-	if script.contains("window.opener") && script.contains("location.replace") {
-		let regex_redirect = Regex::new(r#";[^.]location\.replace\(['"](.+)['"]\)"#).unwrap();
-
-		if let Some(url) = regex_redirect.captures(&script) {
-			let request = Request::new(&url[1], HttpMethod::Get)
-				.header("User-Agent", USER_AGENT)
-				.html()?;
-
-			return get_redirect_read_page(request);
-		}
-	}
-
-	Ok(html)
-}
-
 #[get_chapter_list]
 fn get_chapter_list(id: String) -> Result<Vec<Chapter>> {
 	let url = if id.starts_with("http") {
@@ -359,10 +288,11 @@ fn get_chapter_list(id: String) -> Result<Vec<Chapter>> {
 	} else {
 		format!("{BASE_URL}{id}")
 	};
-	let html = Request::new(url, HttpMethod::Get)
+	let html = ChallengeRequest::get(url)
 		.header("User-Agent", USER_AGENT)
 		.header("Referer", BASE_URL)
-		.html()?;
+		.html()
+		.map_err(ChallengeError::report)?;
 
 	let chapter_elements = html.select("div.chapters > ul.list-group li.p-0.list-group-item");
 
@@ -427,20 +357,22 @@ fn get_page_list(_manga_id: String, chapter_id: String) -> Result<Vec<Page>> {
 		format!("{BASE_URL}{chapter_id}")
 	};
 
-	let mut html = Request::new(url, HttpMethod::Get)
+	// the reader is behind a few script redirects and a `uniqid` form
+	let mut html = ChallengeRequest::get(url)
 		.header("User-Agent", USER_AGENT)
 		.header("Referer", BASE_URL)
-		.html()?;
-
-	html = get_redirect_read_page(html)?;
+		.follow_script_redirects()
+		.html()
+		.map_err(ChallengeError::report)?;
 
 	let uri = html.base_uri().read();
 	if uri.contains("/paginated") {
 		// switch to cascade for full image list
-		html = Request::new(uri.replace("/paginated", "/cascade"), HttpMethod::Get)
+		html = ChallengeRequest::get(uri.replace("/paginated", "/cascade"))
 			.header("User-Agent", USER_AGENT)
 			.header("Referer", BASE_URL)
-			.html()?;
+			.html()
+			.map_err(ChallengeError::report)?;
 	}
 
 	let mut pages: Vec<Page> = Vec::new();
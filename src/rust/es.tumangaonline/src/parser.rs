@@ -1,18 +1,21 @@
 use aidoku::{
 	error::AidokuError,
 	helpers::substring::Substring,
-	std::{net::HttpMethod, net::Request, String, Vec},
+	std::{String, Vec},
 	Manga, MangaPageResult,
 };
 use alloc::{borrow::ToOwned, string::ToString};
 
+use challenge::{ChallengeError, ChallengeRequest};
+
 use crate::{BASE_URL, USER_AGENT};
 
 pub fn parse_manga_list(url: String) -> Result<MangaPageResult, AidokuError> {
-	let html = Request::new(url, HttpMethod::Get)
+	let html = ChallengeRequest::get(url)
 		.header("User-Agent", USER_AGENT)
 		.header("Referer", BASE_URL)
-		.html()?;
+		.html()
+		.map_err(ChallengeError::report)?;
 
 	let elements = html.select("div.element");
 
@@ -0,0 +1,138 @@
+#![no_std]
+use aidoku::{
+	error::Result,
+	prelude::*,
+	std::String,
+	std::{ObjectRef, Vec},
+	Chapter, DeepLink, Filter, FilterType, Manga, MangaContentRating, MangaPageResult, Page,
+};
+use guya_template::template;
+
+fn data() -> template::GuyaSiteData {
+	template::GuyaSiteData {
+		base_url: String::from("https://cubari.moe"),
+		proxy: true,
+		..Default::default()
+	}
+}
+
+/// Returns the `{source}/{slug}` id of the series behind a cubari.moe read
+/// url, a raw gist link or a git.io link.
+fn get_series_id(url: &str) -> Option<String> {
+	let url = url.trim();
+	let url = url.split(['?', '#']).next().unwrap_or(url);
+	let (host, path) = url
+		.split_once("://")
+		.map(|(_, rest)| rest)
+		.unwrap_or(url)
+		.split_once('/')?;
+	let parts = path
+		.split('/')
+		.filter(|part| !part.is_empty())
+		.collect::<Vec<_>>();
+	match host.trim_start_matches("www.") {
+		"cubari.moe" => match parts.as_slice() {
+			["read", "api", source, "series", slug, ..] | ["read", source, slug, ..] => {
+				Some(format!("{}/{}", source, slug))
+			}
+			_ => None,
+		},
+		// cubari identifies gists by their base64 encoded raw path
+		"gist.githubusercontent.com" => Some(format!(
+			"gist/{}",
+			base64::encode_config(format!("raw/{}", path), base64::URL_SAFE_NO_PAD)
+		)),
+		"git.io" => parts.first().map(|slug| format!("gist/{}", slug)),
+		_ => None,
+	}
+}
+
+fn get_content_rating(slug: &str) -> MangaContentRating {
+	if slug.starts_with("nhentai/") {
+		MangaContentRating::Nsfw
+	} else {
+		MangaContentRating::Safe
+	}
+}
+
+/// Cubari has no series list of its own, so searching for a proxied link
+/// returns the series behind it.
+#[get_manga_list]
+fn get_manga_list(filters: Vec<Filter>, _: i32) -> Result<MangaPageResult> {
+	let mut manga = Vec::new();
+	for filter in filters {
+		match filter.kind {
+			FilterType::Title => {
+				let query = filter.value.as_string()?.read();
+				if let Some(slug) = get_series_id(&query) {
+					if let Ok(details) = get_manga_details(slug) {
+						manga.push(details);
+					}
+				}
+			}
+			_ => continue,
+		}
+	}
+	Ok(MangaPageResult {
+		manga,
+		has_more: false,
+	})
+}
+
+#[get_manga_details]
+pub fn get_manga_details(slug: String) -> Result<Manga> {
+	let nsfw = get_content_rating(&slug);
+	template::get_manga_details(data(), slug, nsfw)
+}
+
+#[get_chapter_list]
+pub fn get_chapter_list(slug: String) -> Result<Vec<Chapter>> {
+	template::get_chapter_list(data(), slug)
+}
+
+/// # Safety
+///
+/// `rid` must be a descriptor of the chapter object passed by the app, as
+/// the full object is needed to find the series of proxied chapters.
+#[no_mangle]
+#[export_name = "get_page_list"]
+pub unsafe extern "C" fn __wasm_get_page_list(rid: i32) -> i32 {
+	let obj = aidoku::std::ObjectRef(aidoku::std::ValueRef::new(rid));
+	let resp: Result<Vec<Page>> = get_page_list(obj);
+	match resp {
+		Ok(resp) => {
+			let mut arr = aidoku::std::ArrayRef::new();
+			for item in resp {
+				let rid = item.create();
+				arr.insert(aidoku::std::ValueRef::new(rid));
+			}
+			let rid = arr.0 .0;
+			core::mem::forget(arr.0);
+			rid
+		}
+		Err(_) => -1,
+	}
+}
+
+pub fn get_page_list(chapter: ObjectRef) -> Result<Vec<Page>> {
+	template::get_page_list(data(), chapter)
+}
+
+#[handle_url]
+pub fn handle_url(url: String) -> Result<DeepLink> {
+	let slug = match get_series_id(&url) {
+		Some(slug) => slug,
+		None => {
+			return Ok(DeepLink {
+				manga: None,
+				chapter: None,
+			})
+		}
+	};
+	// raw gist links have no chapter
+	let chapter_num = template::parse_reader_url(&data(), &url)
+		.filter(|(id, _)| *id == slug)
+		.and_then(|(_, chapter_num)| chapter_num);
+	let nsfw = get_content_rating(&slug);
+	template::get_deep_link(data(), slug, chapter_num, nsfw)
+}
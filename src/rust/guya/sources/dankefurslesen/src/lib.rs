@@ -8,8 +8,6 @@ use aidoku::{
 	std::{ObjectRef, Vec},
 	Chapter, DeepLink, Filter, Manga, MangaContentRating, MangaPageResult, Page,
 };
-use alloc::string::ToString;
-extern crate alloc;
 use guya_template::template;
 
 fn data() -> template::GuyaSiteData {
@@ -75,8 +73,10 @@ pub fn get_page_list(chapter: ObjectRef) -> Result<Vec<Page>> {
 
 #[handle_url]
 pub fn handle_url(url: String) -> Result<DeepLink> {
-	let parts = url.split('/').collect::<Vec<&str>>();
-	let slug = parts[5].to_string();
+	let slug = match template::parse_reader_url(&data(), &url) {
+		Some((slug, _)) => slug,
+		None => return template::handle_url(data(), url, MangaContentRating::Safe),
+	};
 	let nsfw_url = format!("{}/read/series/{}/", &data().base_url, slug);
 	let request = Request::new(nsfw_url, HttpMethod::Get).header("User-Agent", "Aidoku");
 	let html = request.html()?;
@@ -1,20 +1,25 @@
 use aidoku::{
 	error::Result,
 	prelude::*,
+	std::defaults::defaults_get,
 	std::net::HttpMethod,
 	std::net::Request,
 	std::String,
-	std::{html::Node, ObjectRef, Vec},
+	std::{html::Node, Kind, ObjectRef, ValueRef, Vec},
 	Chapter, DeepLink, Filter, FilterType, Manga, MangaContentRating, MangaPageResult, MangaStatus,
 	MangaViewer, Page,
 };
 use alloc::string::ToString;
+use core::cmp::Ordering;
 extern crate alloc;
 
 pub struct GuyaSiteData {
 	pub base_url: String,
 	pub nsfw: MangaContentRating,
 	pub language: String,
+	/// Whether the series are proxied through Cubari, in which case manga
+	/// ids are `{source}/{slug}` and the series api lives under `/read/api/`.
+	pub proxy: bool,
 }
 
 impl Default for GuyaSiteData {
@@ -23,17 +28,93 @@ impl Default for GuyaSiteData {
 			base_url: String::new(),
 			nsfw: MangaContentRating::Safe,
 			language: String::from("en"),
+			proxy: false,
 		}
 	}
 }
 
+fn get_series_api_url(data: &GuyaSiteData, slug: &str) -> String {
+	if data.proxy {
+		let (source, slug) = slug.split_once('/').unwrap_or(("gist", slug));
+		format!("{}/read/api/{}/series/{}/", &data.base_url, source, slug)
+	} else {
+		format!("{}/api/series/{}/", &data.base_url, slug)
+	}
+}
+
+fn get_reader_url(data: &GuyaSiteData, slug: &str) -> String {
+	if data.proxy {
+		format!("{}/read/{}/", &data.base_url, slug)
+	} else {
+		format!("{}/read/manga/{}/", &data.base_url, slug)
+	}
+}
+
+/// Proxied series link covers and pages to other hosts, while Guya sites
+/// use paths relative to the site.
+fn get_absolute_url(data: &GuyaSiteData, url: &str) -> String {
+	if url.starts_with("http") {
+		String::from(url)
+	} else if url.starts_with("//") {
+		format!("https:{}", url)
+	} else {
+		format!("{}{}", &data.base_url, url)
+	}
+}
+
+fn get_series_json(data: &GuyaSiteData, slug: &str) -> Result<ObjectRef> {
+	let url = get_series_api_url(data, slug);
+	let request = Request::new(url, HttpMethod::Get).header("User-Agent", "Aidoku");
+	request.json()?.as_object()
+}
+
+/// Reads a string that proxies sometimes send as a number instead.
+fn get_string(value: ValueRef) -> Option<String> {
+	match value.kind() {
+		Kind::String => value.as_string().ok().map(|value| value.read()),
+		Kind::Int | Kind::Float => value
+			.as_float()
+			.ok()
+			.map(|value| format!("{:.1}", value).trim_end_matches(".0").to_string()),
+		_ => None,
+	}
+}
+
+/// Whether only the chapters of the series' preferred group are listed,
+/// instead of one chapter per group.
+fn get_preferred_group_only() -> bool {
+	match defaults_get("preferredGroupOnly") {
+		Ok(preferred_group_only) => preferred_group_only.as_bool().unwrap_or(false),
+		Err(_) => false,
+	}
+}
+
+/// Returns the first group of `preferred_sort` that released the chapter,
+/// falling back to the first group that did.
+fn get_preferred_group(preferred_sort: &[String], group_ids: &[String]) -> Option<String> {
+	preferred_sort
+		.iter()
+		.find(|id| group_ids.contains(id))
+		.or_else(|| group_ids.first())
+		.cloned()
+}
+
+/// Returns the chapter keys sorted from newest to oldest, along with their
+/// numbers. The keys are kept as they are since proxies may pad them.
+fn get_chapter_keys(chapters: &ObjectRef) -> Vec<(f32, String)> {
+	let mut keys = chapters
+		.keys()
+		.filter_map(|key| key.as_string().ok().map(|key| key.read()))
+		.filter_map(|key| key.parse::<f32>().ok().map(|number| (number, key)))
+		.collect::<Vec<_>>();
+	keys.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+	keys
+}
+
 pub fn get_manga_list(data: GuyaSiteData, filters: Vec<Filter>, _: i32) -> Result<MangaPageResult> {
 	let url = format!("{}/api/get_all_series/", &data.base_url);
 	let request = Request::new(url, HttpMethod::Get).header("User-Agent", "Aidoku");
-	let mut json = request
-		.json()
-		.expect("Manga list json not found")
-		.as_object()?;
+	let mut json = request.json()?.as_object()?;
 
 	for filter in filters {
 		match filter.kind {
@@ -51,7 +132,7 @@ pub fn get_manga_list(data: GuyaSiteData, filters: Vec<Filter>, _: i32) -> Resul
 	}
 
 	let mut manga_arr: Vec<Manga> = Vec::new();
-	let mut titles: Vec<String> = json.keys().map(|k| k.as_string().unwrap().read()).collect();
+	let mut titles: Vec<String> = json.keys().filter_map(get_string).collect();
 	titles.sort();
 	for title in titles {
 		let obj = match json.get(&title).as_object() {
@@ -62,7 +143,7 @@ pub fn get_manga_list(data: GuyaSiteData, filters: Vec<Filter>, _: i32) -> Resul
 			Ok(slug) => slug.read(),
 			Err(_) => continue,
 		};
-		let cover = format!("{}{}", &data.base_url, obj.get("cover").as_string()?.read());
+		let cover = get_absolute_url(&data, &obj.get("cover").as_string()?.read());
 		manga_arr.push(Manga {
 			id: slug,
 			title,
@@ -85,29 +166,24 @@ pub fn get_manga_details(
 	slug: String,
 	nsfw: MangaContentRating,
 ) -> Result<Manga> {
-	let url = format!("{}/api/series/{}/", &data.base_url, slug);
-	let request = Request::new(url, HttpMethod::Get).header("User-Agent", "Aidoku");
-	let json = request
-		.json()
-		.expect("Manga detail json not found")
-		.as_object()?;
-	let title = json
-		.get("title")
+	let json = get_series_json(&data, &slug)?;
+	let title = json.get("title").as_string()?.read();
+	let cover = json
+		.get("cover")
 		.as_string()
-		.expect("Manga detail title not found")
-		.read();
-	let cover = format!(
-		"{}{}",
-		&data.base_url,
-		json.get("cover").as_string()?.read()
-	);
-	let description_raw = json.get("description").as_string()?.read();
+		.map(|cover| get_absolute_url(&data, &cover.read()))
+		.unwrap_or_default();
+	let description_raw = json
+		.get("description")
+		.as_string()
+		.map(|description| description.read())
+		.unwrap_or_default();
 	let description_node = Node::new_fragment(description_raw.as_bytes())?;
 	let description = match description_node.select("body").array().get(0).as_node() {
 		Ok(node) => node.own_text().read(),
 		Err(_) => String::from(""),
 	};
-	let user_url = format!("{}/read/manga/{}/", &data.base_url, slug);
+	let user_url = get_reader_url(&data, &slug);
 	let author = match json.get("author").as_string() {
 		Ok(author) => author.read(),
 		Err(_) => String::from("Unknown Author"),
@@ -132,53 +208,54 @@ pub fn get_manga_details(
 }
 
 pub fn get_chapter_list(data: GuyaSiteData, slug: String) -> Result<Vec<Chapter>> {
-	let url = format!("{}/api/series/{}/", &data.base_url, slug);
-	let request = Request::new(url, HttpMethod::Get).header("User-Agent", "Aidoku");
-	let json = request
-		.json()
-		.expect("Manga chapter list json not found")
-		.as_object()?;
+	get_chapters(&data, &slug, get_preferred_group_only())
+}
+
+fn get_chapters(data: &GuyaSiteData, slug: &str, preferred_only: bool) -> Result<Vec<Chapter>> {
+	let json = get_series_json(data, slug)?;
 	let mut chapter_arr: Vec<Chapter> = Vec::new();
 	let chapter_obj = json.get("chapters").as_object()?;
-	let mut chapters: Vec<f32> = chapter_obj
-		.keys()
-		.map(|k| k.as_string().unwrap().read().parse::<f32>().unwrap())
-		.collect();
-	chapters.sort_by(|a, b| b.partial_cmp(a).unwrap());
-	for chapter in chapters {
-		let chapter = chapter.to_string();
+	let group_list = json.get("groups").as_object().ok();
+	let preferred_sort = match json.get("preferred_sort").as_array() {
+		Ok(preferred_sort) => preferred_sort.filter_map(get_string).collect::<Vec<_>>(),
+		Err(_) => Vec::new(),
+	};
+	for (chapter_int, chapter) in get_chapter_keys(&chapter_obj) {
 		let obj = match chapter_obj.get(&chapter).as_object() {
 			Ok(obj) => obj,
 			Err(_) => continue,
 		};
-		let title = obj.get("title").as_string()?.read();
-		let volume = obj
-			.get("volume")
-			.as_string()?
-			.read()
-			.parse()
+		let title = get_string(obj.get("title")).unwrap_or_default();
+		let volume = get_string(obj.get("volume"))
+			.and_then(|volume| volume.parse().ok())
 			.unwrap_or(-1.0);
-		let chapter_int = chapter.parse().unwrap_or(1.0);
-		let user_url = format!("{}/read/manga/{}/{}/", &data.base_url, &slug, chapter);
-		let folder = obj.get("folder").as_string()?.read();
-		for groups in obj.get("groups").as_object()?.keys() {
-			let group_id = groups.as_string()?.read();
-			let mut group_name = String::new();
-			let group_list = json.get("groups").as_object()?;
-			for gl_index in group_list.keys() {
-				let gl_id = gl_index.as_string()?.read();
-				if gl_id == group_id {
-					group_name = group_list.get(&gl_id).as_string()?.read();
-				}
-			}
-			let mut date_updated = 0.0;
-			let date_list = obj.get("release_date").as_object()?;
-			for dl_index in date_list.keys() {
-				let dl_id = dl_index.as_string()?.read();
-				if dl_id == group_id {
-					date_updated = date_list.get(&dl_id).as_float()?;
-				}
-			}
+		let user_url = format!("{}{}/", get_reader_url(data, slug), chapter);
+		// proxied chapters have no folder
+		let folder = get_string(obj.get("folder")).unwrap_or_default();
+		let date_list = obj.get("release_date").as_object().ok();
+		let last_updated = get_string(obj.get("last_updated")).and_then(|date| date.parse().ok());
+		let mut group_ids = obj
+			.get("groups")
+			.as_object()?
+			.keys()
+			.filter_map(get_string)
+			.collect::<Vec<_>>();
+		if preferred_only {
+			group_ids = get_preferred_group(&preferred_sort, &group_ids)
+				.into_iter()
+				.collect();
+		}
+		for group_id in group_ids {
+			// proxies may key the chapter groups by name
+			let group_name = group_list
+				.as_ref()
+				.and_then(|groups| get_string(groups.get(&group_id)))
+				.unwrap_or_else(|| group_id.clone());
+			let date_updated = date_list
+				.as_ref()
+				.and_then(|dates| dates.get(&group_id).as_float().ok())
+				.or(last_updated)
+				.unwrap_or(0.0);
 			let id = format!("{}|{}", &folder, &group_id);
 			chapter_arr.push(Chapter {
 				id,
@@ -197,36 +274,45 @@ pub fn get_chapter_list(data: GuyaSiteData, slug: String) -> Result<Vec<Chapter>
 }
 
 pub fn get_page_list(data: GuyaSiteData, chapter: ObjectRef) -> Result<Vec<Page>> {
-	let slug = chapter
-		.get("mangaId")
-		.as_string()
-		.expect("Manga chapter object slug not found")
-		.read();
-	let url = format!("{}/api/series/{}/", &data.base_url, &slug);
-	let request = Request::new(url, HttpMethod::Get).header("User-Agent", "Aidoku");
-	let json = request
-		.json()
-		.expect("Manga page list json not found")
-		.as_object()?;
-	let chapter_num = chapter.get("chapterNum").as_float()?;
-	let chapter_num = format!("{:.1}", chapter_num)
-		.trim_end_matches(".0")
-		.to_string();
+	let slug = chapter.get("mangaId").as_string()?.read();
+	let json = get_series_json(&data, &slug)?;
+	let chapter_num = chapter.get("chapterNum").as_float()? as f32;
 
 	let ids = chapter.get("id").as_string()?.read();
-	let group_id = ids.split('|').collect::<Vec<&str>>()[1].to_string();
+	let group_id = ids.split_once('|').map(|(_, id)| id).unwrap_or_default();
 	let chapters_obj = json.get("chapters").as_object()?;
-	let chapter_obj = chapters_obj.get(chapter_num.as_str()).as_object()?;
-	let folder = chapter_obj.get("folder").as_string()?.read();
-	let groups_obj = chapter_obj.get("groups").as_object()?;
-	let chapter_array = groups_obj.get(group_id.as_str()).as_array()?;
+	let chapter_key = get_chapter_keys(&chapters_obj)
+		.into_iter()
+		.find(|(number, _)| *number == chapter_num)
+		.map(|(_, key)| key)
+		.unwrap_or_default();
+	let chapter_obj = chapters_obj.get(&chapter_key).as_object()?;
+	let folder = get_string(chapter_obj.get("folder")).unwrap_or_default();
+	let group = chapter_obj.get("groups").as_object()?.get(group_id);
+	// proxies may link to an endpoint listing the pages instead
+	let chapter_array = match group.kind() {
+		Kind::String => {
+			let url = get_absolute_url(&data, &group.as_string()?.read());
+			let request = Request::new(url, HttpMethod::Get).header("User-Agent", "Aidoku");
+			request.json()?.as_array()?
+		}
+		_ => group.as_array()?,
+	};
 	let mut pages: Vec<Page> = Vec::new();
 	for (idx, page) in chapter_array.enumerate() {
-		let page_string = page.as_string()?.read();
-		let page_url = format!(
-			"{}/media/manga/{}/chapters/{}/{}/{}",
-			&data.base_url, &slug, folder, group_id, page_string
-		);
+		// proxied pages are either urls or objects with a `src` url
+		let page_string = match page.kind() {
+			Kind::Object => page.as_object()?.get("src").as_string()?.read(),
+			_ => page.as_string()?.read(),
+		};
+		let page_url = if data.proxy {
+			get_absolute_url(&data, &page_string)
+		} else {
+			format!(
+				"{}/media/manga/{}/chapters/{}/{}/{}",
+				&data.base_url, &slug, folder, group_id, page_string
+			)
+		};
 		pages.push(Page {
 			index: idx as i32,
 			url: page_url,
@@ -237,12 +323,56 @@ pub fn get_page_list(data: GuyaSiteData, chapter: ObjectRef) -> Result<Vec<Page>
 	Ok(pages)
 }
 
-pub fn handle_url(data: GuyaSiteData, url: String, nsfw: MangaContentRating) -> Result<DeepLink> {
-	let parts = url.split('/').collect::<Vec<&str>>();
-	let slug = parts[5].to_string();
+/// Reads the manga id and, if present, the chapter number of a reader url
+/// like `/read/manga/{slug}/{chapter}/{page}/`, where decimal chapters are
+/// written as `12-5`.
+pub fn parse_reader_url(data: &GuyaSiteData, url: &str) -> Option<(String, Option<f32>)> {
+	let path = url.split(['?', '#']).next().unwrap_or(url);
+	let parts = path
+		.split('/')
+		.filter(|part| !part.is_empty())
+		.collect::<Vec<_>>();
+	let read = parts.iter().position(|part| *part == "read")?;
+	let kind = parts.get(read + 1)?;
+	let slug = parts.get(read + 2)?;
+	if *kind == "api" {
+		return None;
+	}
+	let slug = if data.proxy {
+		format!("{}/{}", kind, slug)
+	} else {
+		slug.to_string()
+	};
+	let chapter = parts
+		.get(read + 3)
+		.and_then(|chapter| chapter.replace('-', ".").parse::<f32>().ok());
+	Some((slug, chapter))
+}
+
+/// Returns the manga and, when a chapter number is given, that chapter as
+/// released by the preferred group.
+pub fn get_deep_link(
+	data: GuyaSiteData,
+	slug: String,
+	chapter_num: Option<f32>,
+	nsfw: MangaContentRating,
+) -> Result<DeepLink> {
+	let chapter = chapter_num.and_then(|chapter_num| {
+		get_chapters(&data, &slug, true)
+			.ok()?
+			.into_iter()
+			.find(|chapter| chapter.chapter == chapter_num)
+	});
 	let manga = get_manga_details(data, slug, nsfw).ok();
-	Ok(DeepLink {
-		manga,
-		chapter: None,
-	})
+	Ok(DeepLink { manga, chapter })
+}
+
+pub fn handle_url(data: GuyaSiteData, url: String, nsfw: MangaContentRating) -> Result<DeepLink> {
+	match parse_reader_url(&data, &url) {
+		Some((slug, chapter_num)) => get_deep_link(data, slug, chapter_num, nsfw),
+		None => Ok(DeepLink {
+			manga: None,
+			chapter: None,
+		}),
+	}
 }
@@ -1,15 +1,33 @@
 # template source build script
 # usage: ./build.sh [source_name/-a]
 
+# packages a site's res folder, adding its site.json to settings.json as the
+# default of the siteJson setting the json crate reads it from
+package_site() {
+    cp $1/* target/wasm32-unknown-unknown/release/Payload
+    rm target/wasm32-unknown-unknown/release/Payload/site.json
+    (cat $1/settings.json 2>/dev/null || echo "[]") | jq --tab --arg site "$(jq -c . $1/site.json)" \
+        '. + [{"type": "group", "title": "Site", "items": [{"type": "page", "title": "Site Definition", "items": [{"type": "text", "key": "siteJson", "placeholder": "site.json", "default": $site}]}]}]' \
+        > target/wasm32-unknown-unknown/release/Payload/settings.json
+}
+
 if [ "$1" != "-a" ] && [ "$1" != "" ]; then
     # compile specified source
     cargo +nightly build --release
-    
+
     echo "packaging $1";
     mkdir -p target/wasm32-unknown-unknown/release/Payload
-    cp sources/$1/res/* target/wasm32-unknown-unknown/release/Payload
+    if [ -d sites/$1 ]; then
+        # sources configured by a site.json share the json crate
+        package_site sites/$1/res
+        wasm=madara_json.wasm
+    else
+        cp sources/$1/res/* target/wasm32-unknown-unknown/release/Payload
+        wasm=$1.wasm
+    fi
+
     cd target/wasm32-unknown-unknown/release
-    cp $1.wasm Payload/main.wasm
+    cp $wasm Payload/main.wasm
     zip -r $1.aix Payload
     mv $1.aix ../../../$1.aix
     rm -rf Payload
@@ -32,4 +50,21 @@ else
         rm -rf Payload
         cd ../../../
     done
+
+    # sources configured by a site.json share the json crate
+    for dir in sites/*/
+    do
+        dir=${dir%*/}
+        dir=${dir##*/}
+        echo "packaging $dir";
+
+        mkdir -p target/wasm32-unknown-unknown/release/Payload
+        package_site sites/$dir/res
+        cd target/wasm32-unknown-unknown/release
+        cp madara_json.wasm Payload/main.wasm
+        zip -r $dir.aix Payload >> /dev/null
+        mv $dir.aix ../../../$dir.aix
+        rm -rf Payload
+        cd ../../../
+    done
 fi
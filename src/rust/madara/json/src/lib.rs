@@ -0,0 +1,57 @@
+#![no_std]
+use aidoku::{
+	error::Result, prelude::*, std::defaults::defaults_get, std::String, std::Vec, Chapter,
+	DeepLink, Filter, Listing, Manga, MangaPageResult, Page,
+};
+use core::ptr;
+
+use madara_template::template;
+
+// Madara sources which only differ by their site data share this crate,
+// which reads the site.json of their res folder at runtime. build.sh packages
+// it as the default of the `siteJson` setting, the only resource a source
+// can read.
+const SITE_JSON_KEY: &str = "siteJson";
+
+// The site data parsed from the site.json on first use
+static mut SITE_DATA: Option<template::MadaraSiteData> = None;
+
+fn get_data() -> Result<template::MadaraSiteData> {
+	if let Some(data) = unsafe { &*ptr::addr_of!(SITE_DATA) } {
+		return Ok(data.clone());
+	}
+	let site_json = defaults_get(SITE_JSON_KEY)?.as_string()?.read();
+	let data = template::MadaraSiteData::from_json(&site_json)?;
+	unsafe { SITE_DATA = Some(data.clone()) };
+	Ok(data)
+}
+
+#[get_manga_list]
+fn get_manga_list(filters: Vec<Filter>, page: i32) -> Result<MangaPageResult> {
+	template::get_manga_list(filters, page, get_data()?)
+}
+
+#[get_manga_listing]
+fn get_manga_listing(listing: Listing, page: i32) -> Result<MangaPageResult> {
+	template::get_manga_listing(get_data()?, listing, page)
+}
+
+#[get_manga_details]
+fn get_manga_details(id: String) -> Result<Manga> {
+	template::get_manga_details(id, get_data()?)
+}
+
+#[get_chapter_list]
+fn get_chapter_list(id: String) -> Result<Vec<Chapter>> {
+	template::get_chapter_list(id, get_data()?)
+}
+
+#[get_page_list]
+fn get_page_list(_manga_id: String, chapter_id: String) -> Result<Vec<Page>> {
+	template::get_page_list(chapter_id, get_data()?)
+}
+
+#[handle_url]
+pub fn handle_url(url: String) -> Result<DeepLink> {
+	template::handle_url(url, get_data()?)
+}
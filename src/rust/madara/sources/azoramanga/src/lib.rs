@@ -11,6 +11,8 @@ fn get_data() -> template::MadaraSiteData {
 		base_url: String::from("https://azoranov.com"),
 		source_path: String::from("series"),
 		alt_ajax: true,
+		artist_filter: String::from("الرسام"),
+		release_filter: String::from("سنة الإصدار"),
 		..Default::default()
 	};
 	data
@@ -60,6 +60,8 @@ fn get_data() -> template::MadaraSiteData {
 		status_filter_on_hold: String::from("Tạm ngưng/Ngang raw"),
 		adult_string: String::from("Truyện 18+"),
 		genre_condition: String::from("Điều kiện lọc thể loại"),
+		artist_filter: String::from("Họa sĩ"),
+		release_filter: String::from("Năm phát hành"),
 		trending: String::from("Truyện hot"),
 		popular: String::from("Phổ biến"),
 		..Default::default()
@@ -0,0 +1,224 @@
+use aidoku::{
+	error::Result,
+	std::{html::Node, json, ObjectRef, String, Vec},
+	MangaContentRating, MangaStatus, MangaViewer,
+};
+
+use crate::template::MadaraSiteData;
+
+/// Status keywords, matched against the lowercased text of `selector`.
+#[derive(Clone)]
+pub struct StatusKeywords {
+	pub selector: String,
+	pub ongoing: Vec<String>,
+	pub completed: Vec<String>,
+	pub cancelled: Vec<String>,
+	pub hiatus: Vec<String>,
+}
+
+impl StatusKeywords {
+	pub fn get_status(&self, html: &Node) -> MangaStatus {
+		let status = html.select(&self.selector).text().read().to_lowercase();
+		let status = status.trim();
+		if contains_keyword(&self.ongoing, status) {
+			MangaStatus::Ongoing
+		} else if contains_keyword(&self.completed, status) {
+			MangaStatus::Completed
+		} else if contains_keyword(&self.cancelled, status) {
+			MangaStatus::Cancelled
+		} else if contains_keyword(&self.hiatus, status) {
+			MangaStatus::Hiatus
+		} else {
+			MangaStatus::Unknown
+		}
+	}
+}
+
+/// Viewer keywords, looked for in the series type text or the categories.
+#[derive(Clone)]
+pub struct ViewerKeywords {
+	pub selector: String,
+	pub scroll: Vec<String>,
+	pub rtl: Vec<String>,
+	pub default: MangaViewer,
+}
+
+impl ViewerKeywords {
+	pub fn get_viewer(&self, html: &Node, categories: &[String]) -> MangaViewer {
+		let series_type = html.select(&self.selector).text().read().to_lowercase();
+		let matches = |keywords: &Vec<String>| {
+			if series_type.is_empty() {
+				categories
+					.iter()
+					.any(|category| contains_keyword(keywords, &category.to_lowercase()))
+			} else {
+				keywords
+					.iter()
+					.any(|keyword| series_type.contains(keyword.as_str()))
+			}
+		};
+		if matches(&self.scroll) {
+			MangaViewer::Scroll
+		} else if matches(&self.rtl) {
+			MangaViewer::Rtl
+		} else {
+			self.default
+		}
+	}
+}
+
+/// Content rating keywords, compared to the lowercased categories.
+#[derive(Clone)]
+pub struct NsfwKeywords {
+	pub nsfw: Vec<String>,
+	pub suggestive: Vec<String>,
+}
+
+impl NsfwKeywords {
+	pub fn get_nsfw(&self, html: &Node, categories: &[String]) -> MangaContentRating {
+		if !html
+			.select(".manga-title-badges.adult")
+			.text()
+			.read()
+			.is_empty()
+		{
+			return MangaContentRating::Nsfw;
+		}
+		let matches = |keywords: &Vec<String>| {
+			categories
+				.iter()
+				.any(|category| contains_keyword(keywords, &category.to_lowercase()))
+		};
+		if matches(&self.nsfw) {
+			MangaContentRating::Nsfw
+		} else if matches(&self.suggestive) {
+			MangaContentRating::Suggestive
+		} else {
+			MangaContentRating::Safe
+		}
+	}
+}
+
+fn contains_keyword(keywords: &[String], value: &str) -> bool {
+	keywords.iter().any(|keyword| keyword == value)
+}
+
+fn read_string(object: &ObjectRef, key: &str, field: &mut String) {
+	if let Ok(value) = object.get(key).as_string() {
+		*field = value.read();
+	}
+}
+
+fn read_bool(object: &ObjectRef, key: &str, field: &mut bool) {
+	if let Ok(value) = object.get(key).as_bool() {
+		*field = value;
+	}
+}
+
+fn read_int(object: &ObjectRef, key: &str, field: &mut i32) {
+	if let Ok(value) = object.get(key).as_int() {
+		*field = value as i32;
+	}
+}
+
+fn read_keywords(object: &ObjectRef, key: &str) -> Vec<String> {
+	match object.get(key).as_array() {
+		Ok(array) => array
+			.filter_map(|value| value.as_string().ok())
+			.map(|value| value.read().to_lowercase())
+			.collect(),
+		Err(_) => Vec::new(),
+	}
+}
+
+impl MadaraSiteData {
+	/// Builds the site data from a `site.json` configuration. Every key is
+	/// optional and named after the field it overrides, except for the
+	/// `status`, `viewer` and `nsfw` keyword tables which replace the
+	/// corresponding hooks.
+	pub fn from_json(site_json: &str) -> Result<MadaraSiteData> {
+		let config = json::parse(site_json.as_bytes())?.as_object()?;
+		let mut data = MadaraSiteData::default();
+
+		for (key, field) in [
+			("base_url", &mut data.base_url),
+			("lang", &mut data.lang),
+			("source_path", &mut data.source_path),
+			("search_path", &mut data.search_path),
+			("search_cookies", &mut data.search_cookies),
+			("post_type", &mut data.post_type),
+			("search_selector", &mut data.search_selector),
+			("image_selector", &mut data.image_selector),
+			("genre_selector", &mut data.genre_selector),
+			("author_selector", &mut data.author_selector),
+			("description_selector", &mut data.description_selector),
+			("chapter_selector", &mut data.chapter_selector),
+			("base_id_selector", &mut data.base_id_selector),
+			("scanlator_selector", &mut data.scanlator_selector),
+			("date_format", &mut data.date_format),
+			("status_filter_ongoing", &mut data.status_filter_ongoing),
+			("status_filter_completed", &mut data.status_filter_completed),
+			("status_filter_cancelled", &mut data.status_filter_cancelled),
+			("status_filter_on_hold", &mut data.status_filter_on_hold),
+			("adult_string", &mut data.adult_string),
+			("genre_condition", &mut data.genre_condition),
+			("artist_filter", &mut data.artist_filter),
+			("release_filter", &mut data.release_filter),
+			("popular", &mut data.popular),
+			("trending", &mut data.trending),
+			("ignore_class", &mut data.ignore_class),
+		] {
+			read_string(&config, key, field);
+		}
+
+		read_bool(&config, "alt_ajax", &mut data.alt_ajax);
+		read_bool(&config, "use_ajax_listing", &mut data.use_ajax_listing);
+		read_bool(&config, "novel", &mut data.novel);
+		read_int(&config, "posts_per_page", &mut data.posts_per_page);
+
+		if let Ok(user_agent) = config.get("user_agent").as_string() {
+			data.user_agent = Some(user_agent.read());
+		}
+
+		if let Ok(status) = config.get("status").as_object() {
+			let mut selector =
+				String::from("div.post-content_item:contains(Status) div.summary-content");
+			read_string(&status, "selector", &mut selector);
+			data.status_keywords = Some(StatusKeywords {
+				selector,
+				ongoing: read_keywords(&status, "ongoing"),
+				completed: read_keywords(&status, "completed"),
+				cancelled: read_keywords(&status, "cancelled"),
+				hiatus: read_keywords(&status, "hiatus"),
+			});
+		}
+
+		if let Ok(viewer) = config.get("viewer").as_object() {
+			let mut selector =
+				String::from("div.post-content_item:contains(Type) div.summary-content");
+			read_string(&viewer, "selector", &mut selector);
+			let mut default = String::from("scroll");
+			read_string(&viewer, "default", &mut default);
+			data.viewer_keywords = Some(ViewerKeywords {
+				selector,
+				scroll: read_keywords(&viewer, "scroll"),
+				rtl: read_keywords(&viewer, "rtl"),
+				default: match default.as_str() {
+					"rtl" => MangaViewer::Rtl,
+					"ltr" => MangaViewer::Ltr,
+					"vertical" => MangaViewer::Vertical,
+					_ => MangaViewer::Scroll,
+				},
+			});
+		}
+
+		if let Ok(nsfw) = config.get("nsfw").as_object() {
+			data.nsfw_keywords = Some(NsfwKeywords {
+				nsfw: read_keywords(&nsfw, "nsfw"),
+				suggestive: read_keywords(&nsfw, "suggestive"),
+			});
+		}
+
+		Ok(data)
+	}
+}
@@ -0,0 +1,79 @@
+use aes::{
+	cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit},
+	Aes256,
+};
+use aidoku::std::{html::Node, json, String, Vec};
+use evpkdf::evpkdf;
+use md5::Md5;
+
+extern crate alloc;
+use alloc::string::ToString;
+
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+/// Returns the source of the "chapter-protector" script, which is either
+/// inlined or embedded as a base64 data uri in the `src` attribute.
+pub fn get_protector_script(html: &Node) -> Option<String> {
+	let node = html.select("script#chapter-protector-data");
+	let src = node.attr("src").read();
+	let script = match src.strip_prefix("data:text/javascript;base64,") {
+		Some(encoded) => String::from_utf8(base64::decode(encoded).ok()?).ok()?,
+		None => node.html().read(),
+	};
+	if script.contains("chapter_data") {
+		Some(script)
+	} else {
+		None
+	}
+}
+
+/// Reads the string assigned to `name` in a script, e.g. `var name = '...';`.
+fn get_script_variable(script: &str, name: &str) -> Option<String> {
+	let start = script.find(name)? + name.len();
+	let value = script[start..].trim_start().strip_prefix('=')?.trim_start();
+	let quote = value.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+	let value = &value[1..];
+	let end = value.find(quote)?;
+	Some(value[..end].replace("\\/", "/"))
+}
+
+/// Decrypts the CryptoJS AES payload of the "chapter-protector" script and
+/// returns the image urls it contains.
+pub fn decrypt_chapter_data(script: &str) -> Option<Vec<String>> {
+	let password = get_script_variable(script, "wpmangaprotectornonce")?;
+	let chapter_data = get_script_variable(script, "chapter_data")?;
+	let chapter_data = json::parse(chapter_data.as_bytes())
+		.ok()?
+		.as_object()
+		.ok()?;
+
+	let mut ciphertext = base64::decode(chapter_data.get("ct").as_string().ok()?.read()).ok()?;
+	let salt = hex::decode(chapter_data.get("s").as_string().ok()?.read()).ok()?;
+
+	// CryptoJS derives a 256-bit key and the iv with OpenSSL's EVP_BytesToKey
+	let mut key_iv = [0u8; 48];
+	evpkdf::<Md5>(password.as_bytes(), &salt, 1, &mut key_iv);
+	let (key, iv) = key_iv.split_at(32);
+
+	let plaintext = Aes256CbcDec::new(key.into(), iv.into())
+		.decrypt_padded_mut::<Pkcs7>(&mut ciphertext)
+		.ok()?;
+
+	// the decrypted data is a json string which itself holds the json array
+	let value = json::parse(plaintext).ok()?;
+	let array = match value.as_string() {
+		Ok(string) => json::parse(string.read().as_bytes())
+			.ok()?
+			.as_array()
+			.ok()?,
+		Err(_) => value.as_array().ok()?,
+	};
+
+	Some(
+		array
+			.filter_map(|item| item.as_string().ok())
+			.map(|url| url.read().trim().to_string())
+			.filter(|url| !url.is_empty())
+			.collect(),
+	)
+}
@@ -1,6 +1,6 @@
 use aidoku::{
-	std::defaults::defaults_get, std::html::Node, std::net::HttpMethod, std::net::Request,
-	std::String, std::Vec, Filter, FilterType,
+	std::current_date, std::defaults::defaults_get, std::html::Node, std::net::HttpMethod,
+	std::net::Request, std::String, std::Vec, Filter, FilterType,
 };
 
 use crate::template::MadaraSiteData;
@@ -63,27 +63,84 @@ pub fn img_url_encode(string: String) -> String {
 	String::from_utf8(result).unwrap_or_default()
 }
 
+/// Picks the candidate with the largest width descriptor from a srcset, or
+/// the smallest one when `largest` is false.
+pub fn get_srcset_url(srcset: &str, largest: bool) -> Option<String> {
+	let candidates = srcset.split(',').filter_map(|candidate| {
+		let mut parts = candidate.split_whitespace();
+		let url = parts.next()?;
+		// "350w" or "2x", candidates without a descriptor count as 1x
+		let width = match parts.next() {
+			Some(descriptor) if descriptor.ends_with('w') => descriptor
+				.trim_end_matches('w')
+				.parse::<f32>()
+				.unwrap_or(0.0),
+			Some(descriptor) if descriptor.ends_with('x') => {
+				descriptor
+					.trim_end_matches('x')
+					.parse::<f32>()
+					.unwrap_or(1.0) * 1000.0
+			}
+			_ => 1000.0,
+		};
+		Some((url, width))
+	});
+	let candidate = if largest {
+		candidates.max_by(|a, b| a.1.total_cmp(&b.1))
+	} else {
+		candidates.min_by(|a, b| a.1.total_cmp(&b.1))
+	};
+	candidate.map(|(url, _)| String::from(url))
+}
+
+/// Removes the WordPress thumbnail size suffix from an image url, e.g.
+/// `cover-193x278.jpg` becomes `cover.jpg`.
+pub fn remove_thumbnail_suffix(url: &str) -> String {
+	let (path, query) = url.split_at(url.find('?').unwrap_or(url.len()));
+	let name_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+	let extension_start = path[name_start..]
+		.rfind('.')
+		.map(|i| name_start + i)
+		.unwrap_or(path.len());
+	let stem = &path[..extension_start];
+	if let Some(dash) = stem[name_start..].rfind('-').map(|i| name_start + i) {
+		if let Some((width, height)) = stem[dash + 1..].split_once('x') {
+			let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+			if is_number(width) && is_number(height) {
+				return String::from(&stem[..dash]) + &path[extension_start..] + query;
+			}
+		}
+	}
+	String::from(url)
+}
+
 pub fn get_image_url(obj: Node) -> String {
-	let mut img = obj.attr("data-src").read();
+	let highres = defaults_get("highres")
+		.and_then(|highres| highres.as_bool())
+		.unwrap_or(false);
+
+	let mut srcset = obj.attr("data-srcset").read();
+	if srcset.is_empty() {
+		srcset = obj.attr("data-lazy-srcset").read();
+	}
+	if srcset.is_empty() {
+		srcset = obj.attr("srcset").read();
+	}
+
+	let mut img = get_srcset_url(&srcset, highres).unwrap_or_default();
 	if img.is_empty() {
-		img = obj.attr("data-lazy-src").read();
+		img = obj.attr("data-src").read();
 	}
 	if img.is_empty() {
-		img = obj.attr("src").read();
+		img = obj.attr("data-lazy-src").read();
 	}
 	if img.is_empty() {
-		img = obj.attr("srcset").read();
+		img = obj.attr("src").read();
 	}
 	img = String::from(img.trim());
 
-	if let Ok(highres) = defaults_get("highres") {
-		if highres.as_bool().unwrap_or(false) && !img.contains("width") {
-			img = img
-				.replace("-350x476", "")
-				.replace("-193x278", "")
-				.replace("-110x150", "")
-				.replace("-175x238", "");
-		}
+	if highres {
+		img = remove_thumbnail_suffix(&img);
 	}
 	// encoding last part of the url as some scanlations use non-alphanumerical
 	// chars which need to be encoded
@@ -117,8 +174,46 @@ pub fn get_filtered_url(filters: Vec<Filter>, page: i32, data: &MadaraSiteData)
 			}
 			FilterType::Author => {
 				if let Ok(filter_value) = filter.value.as_string() {
+					let filter_value = filter_value.read();
+					if filter_value.is_empty() {
+						continue;
+					}
 					query.push_str("&author=");
-					query.push_str(&urlencode(filter_value.read()));
+					query.push_str(&urlencode(filter_value));
+					is_searching = true;
+				}
+			}
+			FilterType::Text => {
+				if let Ok(filter_value) = filter.value.as_string() {
+					let filter_value = filter_value.read();
+					if filter_value.is_empty() {
+						continue;
+					}
+					if filter.name == data.artist_filter {
+						query.push_str("&artist=");
+					} else if filter.name == data.release_filter {
+						query.push_str("&release=");
+					} else {
+						continue;
+					}
+					query.push_str(&urlencode(filter_value));
+					is_searching = true;
+				}
+			}
+			FilterType::Sort => {
+				if let Ok(filter_value) = filter.value.as_object() {
+					let order = match filter_value.get("index").as_int().unwrap_or(0) {
+						1 => "latest",
+						2 => "alphabet",
+						3 => "rating",
+						4 => "trending",
+						5 => "views",
+						6 => "new-manga",
+						_ => continue, // relevance
+					};
+					query.push_str("&m_orderby=");
+					query.push_str(order);
+					is_searching = true;
 				}
 			}
 			FilterType::Check => {
@@ -184,6 +279,295 @@ pub fn get_filtered_url(filters: Vec<Filter>, page: i32, data: &MadaraSiteData)
 	(url, is_searching)
 }
 
+fn parse_leading_number(string: &str) -> Option<f32> {
+	let number = string
+		.chars()
+		.take_while(|c| c.is_ascii_digit() || *c == '.')
+		.collect::<String>();
+	number.trim_end_matches('.').parse::<f32>().ok()
+}
+
+/*  Volume number is the number following a volume prefix in the url or the
+	chapter title, -1.0 if there is none
+	e.g.
+	one-piece-color-jk-english/volume-20-showdown-at-alubarna/chapter-177-30-million-vs-81-million/
+	will return 20
+	"Vol. 3 Ch. 12 - Title"
+	will return 3
+*/
+pub fn get_volume_number(url: &str, title: &str) -> f32 {
+	for segment in url.split('/') {
+		let segment = segment.to_lowercase();
+		for prefix in ["volume-", "volume_", "vol-", "vol_", "tome-"] {
+			if let Some(volume) = segment.strip_prefix(prefix).and_then(parse_leading_number) {
+				return volume;
+			}
+		}
+	}
+
+	let title = title.to_lowercase();
+	for prefix in ["volume", "vol.", "vol ", "tome"] {
+		if let Some(index) = title.find(prefix) {
+			let rest = title[index + prefix.len()..].trim_start_matches([' ', '.', ':']);
+			if let Some(volume) = parse_leading_number(rest) {
+				return volume;
+			}
+		}
+	}
+	-1.0
+}
+
+// www.example.com -> example.com
+pub fn get_site_name(base_url: &str) -> String {
+	let host = base_url
+		.split("://")
+		.last()
+		.unwrap_or(base_url)
+		.split('/')
+		.next()
+		.unwrap_or_default();
+	String::from(host.strip_prefix("www.").unwrap_or(host))
+}
+
+// The words that make a date relative, like "ago" in "2 hours ago", and the
+// words for seconds, minutes, hours, days, weeks, months and years. Both are
+// matched against whole words, as some units like the French "an" or the
+// Vietnamese "năm" also appear in absolute dates.
+type TimeUnits = (
+	&'static str,
+	&'static [&'static str],
+	[&'static [&'static str]; 7],
+);
+
+const TIME_UNITS: [TimeUnits; 8] = [
+	(
+		"en",
+		&["ago"],
+		[
+			&["second", "seconds", "sec", "secs"],
+			&["minute", "minutes", "min", "mins"],
+			&["hour", "hours", "hr", "hrs"],
+			&["day", "days"],
+			&["week", "weeks"],
+			&["month", "months"],
+			&["year", "years"],
+		],
+	),
+	(
+		"es",
+		&["hace"],
+		[
+			&["segundo", "segundos"],
+			&["minuto", "minutos"],
+			&["hora", "horas"],
+			&["día", "días", "dia", "dias"],
+			&["semana", "semanas"],
+			&["mes", "meses"],
+			&["año", "años", "ano", "anos"],
+		],
+	),
+	(
+		"pt",
+		&["há", "atrás"],
+		[
+			&["segundo", "segundos"],
+			&["minuto", "minutos"],
+			&["hora", "horas"],
+			&["dia", "dias"],
+			&["semana", "semanas"],
+			&["mês", "mes", "meses"],
+			&["ano", "anos"],
+		],
+	),
+	(
+		"fr",
+		&["il y a"],
+		[
+			&["seconde", "secondes"],
+			&["minute", "minutes"],
+			&["heure", "heures"],
+			&["jour", "jours"],
+			&["semaine", "semaines"],
+			&["mois"],
+			&["an", "ans", "année", "années"],
+		],
+	),
+	(
+		"tr",
+		&["önce"],
+		[
+			&["saniye"],
+			&["dakika"],
+			&["saat"],
+			&["gün"],
+			&["hafta"],
+			&["ay"],
+			&["yıl"],
+		],
+	),
+	(
+		"vi",
+		&["trước"],
+		[
+			&["giây"],
+			&["phút"],
+			&["giờ"],
+			&["ngày"],
+			&["tuần"],
+			&["tháng"],
+			&["năm"],
+		],
+	),
+	(
+		"id",
+		&["lalu"],
+		[
+			&["detik"],
+			&["menit"],
+			&["jam"],
+			&["hari"],
+			&["minggu"],
+			&["bulan"],
+			&["tahun"],
+		],
+	),
+	// Arabic uses broken plurals, so every form is listed
+	(
+		"ar",
+		&["منذ", "قبل"],
+		[
+			&["ثانية", "ثوان", "ثواني"],
+			&["دقيقة", "دقائق", "دقيقتين"],
+			&["ساعة", "ساعات", "ساعتين"],
+			&["يوم", "أيام", "ايام", "يومين"],
+			&["أسبوع", "أسابيع", "اسبوع", "اسابيع"],
+			&["شهر", "أشهر", "شهور", "شهرين"],
+			&["سنة", "سنوات", "سنتين"],
+		],
+	),
+];
+
+const UNIT_SECONDS: [f64; 7] = [
+	1.0,
+	60.0,
+	60.0 * 60.0,
+	24.0 * 60.0 * 60.0,
+	7.0 * 24.0 * 60.0 * 60.0,
+	30.0 * 24.0 * 60.0 * 60.0,
+	365.0 * 24.0 * 60.0 * 60.0,
+];
+
+// The badge Madara shows instead of a date for fresh chapters
+const NEW_BADGES: [&str; 8] = [
+	"new", "nuevo", "novo", "nouveau", "yeni", "mới", "baru", "جديد",
+];
+
+/// Whether `words` contains the words of `phrase` in a row.
+fn contains_phrase(words: &[&str], phrase: &str) -> bool {
+	let phrase = phrase.split(' ').collect::<Vec<&str>>();
+	words
+		.windows(phrase.len())
+		.any(|window| window == phrase.as_slice())
+}
+
+fn get_unit_seconds(words: &[&str], lang: &str) -> Option<f64> {
+	let find_unit = |(_, markers, units): &TimeUnits| {
+		if !markers.iter().any(|marker| contains_phrase(words, marker)) {
+			return None;
+		}
+		units
+			.iter()
+			.position(|unit| words.iter().any(|word| unit.contains(word)))
+	};
+
+	// the site language isn't always set, so fall back to the other
+	// languages, whose "ago" has to be in the date as well
+	let index = TIME_UNITS
+		.iter()
+		.find(|(code, _, _)| *code == lang)
+		.and_then(find_unit)
+		.or_else(|| TIME_UNITS.iter().find_map(find_unit))?;
+
+	Some(UNIT_SECONDS[index])
+}
+
+/// Parses relative dates like "2 hours ago", "hace 3 días" or "3 gün önce"
+/// for the given site language, as well as the "NEW" badge of fresh chapters.
+pub fn get_relative_date(date_str: &str, lang: &str) -> Option<f64> {
+	let date_str = date_str.trim().to_lowercase();
+	if date_str.is_empty() {
+		return None;
+	}
+	if NEW_BADGES.contains(&date_str.as_str()) {
+		return Some(current_date());
+	}
+
+	let lang = lang.split('-').next().unwrap_or(lang);
+	let words = date_str
+		.split(|c: char| c.is_whitespace() || c == ',' || c == '.')
+		.filter(|word| !word.is_empty())
+		.collect::<Vec<&str>>();
+
+	let unit = get_unit_seconds(&words, lang)?;
+	// "an hour ago", "hace un día" and the like don't have a number
+	let amount = words
+		.iter()
+		.find_map(|word| word.parse::<f64>().ok())
+		.unwrap_or(1.0);
+
+	Some(current_date() - amount * unit)
+}
+
+// www.example.com/{search_path}/{page}/?s=... -> page
+pub fn get_page_from_url(url: &str) -> i32 {
+	url.split('?')
+		.next()
+		.unwrap_or_default()
+		.trim_end_matches('/')
+		.rsplit('/')
+		.next()
+		.and_then(|page| page.parse::<i32>().ok())
+		.unwrap_or(1)
+}
+
+// "max_num_pages":"5" or "max_num_pages":5 in the script holding the query
+// vars of the listing, other scripts like the ones of widgets have their own
+fn get_max_num_pages(html: &Node) -> Option<i32> {
+	html.select("script")
+		.array()
+		.filter_map(|script| script.as_node().ok())
+		.map(|script| script.html().read())
+		.filter(|script| script.contains("query_vars"))
+		.find_map(|script| {
+			let start = script.find("\"max_num_pages\"")? + "\"max_num_pages\"".len();
+			let value = script[start..]
+				.trim_start_matches([':', ' ', '"', '\''])
+				.chars()
+				.take_while(|c| c.is_ascii_digit())
+				.collect::<String>();
+			value.parse::<i32>().ok()
+		})
+}
+
+/// Whether there is a page after `page`, from the max_num_pages value or the
+/// pagination links, None if the page has neither.
+pub fn has_next_page(html: &Node, page: i32) -> Option<bool> {
+	if let Some(max_num_pages) = get_max_num_pages(html) {
+		return Some(page < max_num_pages);
+	}
+	let next_links = html.select(
+		".wp-pagenavi a.nextpostslink, div.nav-previous a, a.next.page-numbers, .pagination a.next",
+	);
+	if !next_links.array().is_empty() {
+		return Some(true);
+	}
+	let pagination = html.select(".wp-pagenavi, div.nav-links, nav.navigation, .pagination");
+	if !pagination.array().is_empty() {
+		return Some(false);
+	}
+	None
+}
+
 pub fn add_user_agent_header(mut req: Request, user_agent: &Option<String>) -> Request {
 	if let Some(agent) = user_agent {
 		req = req.header("User-Agent", agent);
@@ -212,10 +596,14 @@ pub fn get_int_manga_id(
 			return data_id;
 		}
 
+		// admin-ajax is also tried as a fallback, so a missing id must not panic
 		let id_html = html.select("script#wp-manga-js-extra").html().read();
-		let id = &id_html[id_html.find("manga_id").expect("Could not find manga_id") + 11
-			..id_html.find("\"}").expect("Could not find end of manga_id")];
-		String::from(id)
+		match (id_html.find("manga_id"), id_html.find("\"}")) {
+			(Some(start), Some(end)) if start + 11 <= end => {
+				String::from(&id_html[start + 11..end])
+			}
+			_ => String::new(),
+		}
 	} else {
 		String::new()
 	}
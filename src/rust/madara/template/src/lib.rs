@@ -1,3 +1,5 @@
 #![no_std]
+pub mod config;
+pub mod decryptor;
 pub mod helper;
 pub mod template;
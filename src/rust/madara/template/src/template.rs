@@ -1,5 +1,5 @@
 use aidoku::{
-	error::Result,
+	error::{AidokuError, AidokuErrorKind, Result},
 	prelude::*,
 	std::{
 		current_date,
@@ -11,11 +11,12 @@ use aidoku::{
 	MangaViewer, Page,
 };
 
-use crate::helper::*;
+use crate::{config::*, decryptor::*, helper::*};
 
 extern crate alloc;
 use alloc::string::ToString;
 
+#[derive(Clone)]
 pub struct MadaraSiteData {
 	pub base_url: String,
 	pub lang: String,
@@ -33,6 +34,7 @@ pub struct MadaraSiteData {
 	pub description_selector: String,
 	pub chapter_selector: String,
 	pub base_id_selector: String,
+	pub scanlator_selector: String,
 
 	pub date_format: String,
 
@@ -42,19 +44,27 @@ pub struct MadaraSiteData {
 	pub status_filter_on_hold: String,
 	pub adult_string: String,
 	pub genre_condition: String,
+	pub artist_filter: String,
+	pub release_filter: String,
 	pub popular: String,
 	pub trending: String,
 
 	pub alt_ajax: bool,
 	pub user_agent: Option<String>,
 	pub use_ajax_listing: bool,
+	pub posts_per_page: i32,
 
 	pub get_manga_id: fn(String, String, String, Option<String>) -> String,
 	pub viewer: fn(&Node, &Vec<String>) -> MangaViewer,
 	pub status: fn(&Node) -> MangaStatus,
 	pub nsfw: fn(&Node, &Vec<String>) -> MangaContentRating,
 
+	pub status_keywords: Option<StatusKeywords>,
+	pub viewer_keywords: Option<ViewerKeywords>,
+	pub nsfw_keywords: Option<NsfwKeywords>,
+
 	pub ignore_class: String,
+	pub novel: bool,
 }
 
 impl Default for MadaraSiteData {
@@ -81,18 +91,24 @@ impl Default for MadaraSiteData {
 			chapter_selector: String::from("li.wp-manga-chapter"),
 			// a to get the base id from requests to admin-ajax.php
 			base_id_selector: String::from("h3.h5 > a"),
+			// selector for the scanlator inside a chapter list item, the site name is used when
+			// empty or when nothing matches
+			scanlator_selector: String::new(),
 			// chapter date format
 			date_format: String::from("MMM d, yyyy"),
 			// div to select images from a chapter
 			image_selector: String::from("div.page-break > img"),
 			// div to select all the genres
 			genre_selector: String::from("div.genres-content > a"),
-			// choose between two options for chapter list POST request
+			// prefer {source_path}/manga-id/ajax/chapters over admin-ajax.php for the chapter list,
+			// the other endpoint and the series page are tried as fallbacks
 			alt_ajax: false,
 			// user agent for all http requests
 			user_agent: None,
 			// use admin-ajax to get listings
 			use_ajax_listing: true,
+			// number of series requested per admin-ajax listing page
+			posts_per_page: 20,
 			// get the manga id from script tag
 			get_manga_id: get_int_manga_id,
 			// default viewer
@@ -182,8 +198,15 @@ impl Default for MadaraSiteData {
 					MangaContentRating::Safe
 				}
 			},
+			// keyword tables used instead of the status, viewer and nsfw functions, set from
+			// site.json
+			status_keywords: None,
+			viewer_keywords: None,
+			nsfw_keywords: None,
 			// Ignore MangaPageResult manga with this class from a listing. Usually used for novels.
 			ignore_class: String::from(".web-novel"),
+			// Keep novels in listings and read their chapters as text pages
+			novel: false,
 			// Localization stuff
 			status_filter_ongoing: String::from("Ongoing"),
 			status_filter_completed: String::from("Completed"),
@@ -191,6 +214,8 @@ impl Default for MadaraSiteData {
 			status_filter_on_hold: String::from("On Hold"),
 			adult_string: String::from("Adult Content"),
 			genre_condition: String::from("Genre Condition"),
+			artist_filter: String::from("Artist"),
+			release_filter: String::from("Year of Released"),
 			popular: String::from("Popular"),
 			trending: String::from("Trending"),
 		}
@@ -219,10 +244,11 @@ pub fn get_search_result(data: MadaraSiteData, url: String) -> Result<MangaPageR
 
 	let html = req.html()?;
 	let mut manga: Vec<Manga> = Vec::new();
-	let mut has_more = false;
+	let mut item_count = 0;
 
 	for item in html.select(data.search_selector.as_str()).array() {
 		let obj = item.as_node().expect("node array");
+		item_count += 1;
 
 		let id = obj
 			.select("a")
@@ -236,7 +262,7 @@ pub fn get_search_result(data: MadaraSiteData, url: String) -> Result<MangaPageR
 		let cover = get_image_url(obj.select("img"));
 
 		let genres = obj.select("div.post-content_item div.summary-content a");
-		if genres.text().read().to_lowercase().contains("novel") {
+		if !data.novel && genres.text().read().to_lowercase().contains("novel") {
 			continue;
 		}
 
@@ -253,9 +279,12 @@ pub fn get_search_result(data: MadaraSiteData, url: String) -> Result<MangaPageR
 			nsfw: MangaContentRating::Safe,
 			viewer: MangaViewer::Scroll,
 		});
-		has_more = true;
 	}
 
+	// without pagination links, only a full page can be followed by another
+	let has_more = has_next_page(&html, get_page_from_url(&url))
+		.unwrap_or(item_count >= data.posts_per_page as usize);
+
 	Ok(MangaPageResult { manga, has_more })
 }
 
@@ -279,7 +308,7 @@ pub fn get_series_page(data: MadaraSiteData, listing: &str, page: i32) -> Result
 
 	let url = data.base_url.clone() + "/wp-admin/admin-ajax.php";
 
-	let body_content =  format!("action=madara_load_more&page={}&template=madara-core%2Fcontent%2Fcontent-archive&vars%5Bpaged%5D=1&vars%5Borderby%5D=meta_value_num&vars%5Btemplate%5D=archive&vars%5Bsidebar%5D=full&vars%5Bpost_type%5D=wp-manga&vars%5Bpost_status%5D=publish&vars%5Bmeta_key%5D={}&vars%5Border%5D=desc&vars%5Bmeta_query%5D%5Brelation%5D=OR&vars%5Bmanga_archives_item_layout%5D=big_thumbnail", &page-1, listing);
+	let body_content =  format!("action=madara_load_more&page={}&template=madara-core%2Fcontent%2Fcontent-archive&vars%5Bpaged%5D=1&vars%5Borderby%5D=meta_value_num&vars%5Btemplate%5D=archive&vars%5Bsidebar%5D=full&vars%5Bpost_type%5D=wp-manga&vars%5Bpost_status%5D=publish&vars%5Bmeta_key%5D={}&vars%5Border%5D=desc&vars%5Bmeta_query%5D%5Brelation%5D=OR&vars%5Bmanga_archives_item_layout%5D=big_thumbnail&vars%5Bposts_per_page%5D={}", &page-1, listing, data.posts_per_page);
 
 	let mut req = Request::new(url.as_str(), HttpMethod::Post)
 		.body(body_content.as_bytes())
@@ -290,12 +319,14 @@ pub fn get_series_page(data: MadaraSiteData, listing: &str, page: i32) -> Result
 
 	let html = req.html()?;
 
+	let items = html.select("div.page-item-detail").array();
+	let item_count = items.len();
+
 	let mut manga: Vec<Manga> = Vec::new();
-	let mut has_more = false;
-	for item in html.select("div.page-item-detail").array() {
+	for item in items {
 		let obj = item.as_node().expect("node array");
 
-		if !obj.select(&data.ignore_class).text().read().is_empty() {
+		if !data.novel && !obj.select(&data.ignore_class).text().read().is_empty() {
 			continue;
 		}
 
@@ -337,9 +368,11 @@ pub fn get_series_page(data: MadaraSiteData, listing: &str, page: i32) -> Result
 			nsfw: MangaContentRating::Safe,
 			viewer: MangaViewer::Scroll,
 		});
-		has_more = true;
 	}
 
+	// "load more" returns full pages until the last one
+	let has_more = item_count > 0 && item_count >= data.posts_per_page as usize;
+
 	Ok(MangaPageResult { manga, has_more })
 }
 
@@ -383,9 +416,18 @@ pub fn get_manga_details(manga_id: String, data: MadaraSiteData) -> Result<Manga
 		categories.push(item.as_node().expect("node array").text().read());
 	}
 
-	let status = (data.status)(&html);
-	let viewer = (data.viewer)(&html, &categories);
-	let nsfw = (data.nsfw)(&html, &categories);
+	let status = match &data.status_keywords {
+		Some(keywords) => keywords.get_status(&html),
+		None => (data.status)(&html),
+	};
+	let viewer = match &data.viewer_keywords {
+		Some(keywords) => keywords.get_viewer(&html, &categories),
+		None => (data.viewer)(&html, &categories),
+	};
+	let nsfw = match &data.nsfw_keywords {
+		Some(keywords) => keywords.get_nsfw(&html, &categories),
+		None => (data.nsfw)(&html, &categories),
+	};
 
 	Ok(Manga {
 		id: manga_id,
@@ -402,32 +444,92 @@ pub fn get_manga_details(manga_id: String, data: MadaraSiteData) -> Result<Manga
 	})
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum ChapterEndpoint {
+	// www.example.com/wp-admin/admin-ajax.php
+	AdminAjax,
+	// www.example.com/{source_path}/manga-id/ajax/chapters
+	MangaAjax,
+	// chapters rendered in the series page itself
+	SeriesPage,
+}
+
+// The endpoint that last returned chapters, tried first on the next request
+static mut CHAPTER_ENDPOINT: Option<ChapterEndpoint> = None;
+
 pub fn get_chapter_list(manga_id: String, data: MadaraSiteData) -> Result<Vec<Chapter>> {
-	let mut url = data.base_url.clone() + "/wp-admin/admin-ajax.php";
-	if data.alt_ajax {
-		url = data.base_url.clone()
-			+ "/" + data.source_path.as_str()
-			+ "/" + manga_id.as_str()
-			+ "/ajax/chapters";
+	let mut endpoints: Vec<ChapterEndpoint> = Vec::with_capacity(4);
+	if let Some(endpoint) = unsafe { CHAPTER_ENDPOINT } {
+		endpoints.push(endpoint);
+	}
+	let fallbacks = if data.alt_ajax {
+		[
+			ChapterEndpoint::MangaAjax,
+			ChapterEndpoint::AdminAjax,
+			ChapterEndpoint::SeriesPage,
+		]
+	} else {
+		[
+			ChapterEndpoint::AdminAjax,
+			ChapterEndpoint::MangaAjax,
+			ChapterEndpoint::SeriesPage,
+		]
+	};
+	for endpoint in fallbacks {
+		if !endpoints.contains(&endpoint) {
+			endpoints.push(endpoint);
+		}
 	}
 
-	let int_id = (data.get_manga_id)(
-		manga_id,
-		data.base_url.clone(),
-		data.source_path.clone(),
-		data.user_agent.clone(),
-	);
-	let body_content = format!("action=manga_get_chapters&manga={}", int_id);
-
-	let mut req = Request::new(url.as_str(), HttpMethod::Post)
-		.body(body_content.as_bytes())
-		.header("Referer", &data.base_url)
-		.header("Content-Type", "application/x-www-form-urlencoded");
+	for endpoint in endpoints {
+		let Ok(html) = get_chapter_list_html(endpoint, &manga_id, &data) else {
+			continue;
+		};
+		let chapters = parse_chapter_list(&html, &data);
+		if !chapters.is_empty() {
+			unsafe { CHAPTER_ENDPOINT = Some(endpoint) };
+			return Ok(chapters);
+		}
+	}
+	Ok(Vec::new())
+}
 
-	req = add_user_agent_header(req, &data.user_agent);
+fn get_chapter_list_html(
+	endpoint: ChapterEndpoint,
+	manga_id: &str,
+	data: &MadaraSiteData,
+) -> Result<Node> {
+	let series_url = data.base_url.clone() + "/" + data.source_path.as_str() + "/" + manga_id;
+
+	let req = match endpoint {
+		ChapterEndpoint::AdminAjax => {
+			let int_id = (data.get_manga_id)(
+				manga_id.to_string(),
+				data.base_url.clone(),
+				data.source_path.clone(),
+				data.user_agent.clone(),
+			);
+			let body_content = format!("action=manga_get_chapters&manga={}", int_id);
+			Request::new(
+				(data.base_url.clone() + "/wp-admin/admin-ajax.php").as_str(),
+				HttpMethod::Post,
+			)
+			.body(body_content.as_bytes())
+			.header("Referer", &data.base_url)
+			.header("Content-Type", "application/x-www-form-urlencoded")
+		}
+		ChapterEndpoint::MangaAjax => {
+			Request::new((series_url + "/ajax/chapters").as_str(), HttpMethod::Post)
+				.header("Referer", &data.base_url)
+				.header("Content-Type", "application/x-www-form-urlencoded")
+		}
+		ChapterEndpoint::SeriesPage => Request::new(series_url.as_str(), HttpMethod::Get),
+	};
 
-	let html = req.html()?;
+	add_user_agent_header(req, &data.user_agent).html()
+}
 
+fn parse_chapter_list(html: &Node, data: &MadaraSiteData) -> Vec<Chapter> {
 	let mut chapters: Vec<Chapter> = Vec::new();
 	for item in html.select(&data.chapter_selector).array() {
 		let obj = item.as_node().expect("node array");
@@ -485,36 +587,59 @@ pub fn get_chapter_list(manga_id: String, data: MadaraSiteData) -> Result<Vec<Ch
 			}
 		}
 
-		let date_str = obj.select("span.chapter-release-date > i").text().read();
+		let mut date_str = obj.select("span.chapter-release-date > i").text().read();
+		if date_str.is_empty() {
+			// fresh chapters have a "NEW" badge with the relative date as its title
+			date_str = obj
+				.select("span.chapter-release-date a")
+				.attr("title")
+				.read();
+		}
+		if date_str.is_empty() {
+			date_str = obj.select("span.chapter-release-date").text().read();
+		}
 		let mut date_updated = StringRef::from(&date_str)
 			.0
 			.as_date(data.date_format.as_str(), Some("en"), None)
 			.unwrap_or(-1.0);
-		if date_updated < -1.0 {
+		if date_updated == -1.0 {
 			date_updated = StringRef::from(&date_str)
 				.0
 				.as_date("MMM d, yy", Some("en"), None)
 				.unwrap_or(-1.0);
 		}
+		if date_updated == -1.0 {
+			date_updated = get_relative_date(&date_str, &data.lang).unwrap_or(-1.0);
+		}
 		if date_updated == -1.0 {
 			date_updated = current_date();
 		}
 
+		let volume = get_volume_number(&id, &t_tag);
+
+		let mut scanlator = String::new();
+		if !data.scanlator_selector.is_empty() {
+			scanlator = obj.select(&data.scanlator_selector).text().read();
+		}
+		if scanlator.is_empty() {
+			scanlator = get_site_name(&data.base_url);
+		}
+
 		let url = obj.select("a").attr("href").read();
 		let lang = data.lang.clone();
 
 		chapters.push(Chapter {
 			id,
 			title,
-			volume: -1.0,
+			volume,
 			chapter,
 			date_updated,
-			scanlator: String::new(),
+			scanlator,
 			url,
 			lang,
 		});
 	}
-	Ok(chapters)
+	chapters
 }
 
 pub fn get_page_list(chapter_id: String, data: MadaraSiteData) -> Result<Vec<Page>> {
@@ -525,6 +650,22 @@ pub fn get_page_list(chapter_id: String, data: MadaraSiteData) -> Result<Vec<Pag
 
 	let html = req.html()?;
 
+	// Some sites encrypt the image list with the "chapter-protector" plugin
+	if let Some(urls) = get_protector_script(&html).and_then(|s| decrypt_chapter_data(&s)) {
+		if !urls.is_empty() {
+			return Ok(urls
+				.into_iter()
+				.enumerate()
+				.map(|(index, url)| Page {
+					index: index as i32,
+					url,
+					base64: String::new(),
+					text: String::new(),
+				})
+				.collect());
+		}
+	}
+
 	let mut pages: Vec<Page> = Vec::new();
 	for (index, item) in html
 		.select(data.image_selector.as_str())
@@ -538,21 +679,110 @@ pub fn get_page_list(chapter_id: String, data: MadaraSiteData) -> Result<Vec<Pag
 			text: String::new(),
 		});
 	}
+	if pages.is_empty() && data.novel {
+		pages = get_novel_pages(&html);
+	}
 	Ok(pages)
 }
 
+// Roughly how many characters of a novel chapter go on one page
+const NOVEL_PAGE_LENGTH: usize = 5000;
+
+fn get_novel_pages(html: &Node) -> Vec<Page> {
+	let mut pages: Vec<Page> = Vec::new();
+	let mut text = String::new();
+	for item in html.select("div.reading-content p").array() {
+		let paragraph = item.as_node().expect("node array").text().read();
+		let paragraph = paragraph.trim();
+		if paragraph.is_empty() {
+			continue;
+		}
+		if !text.is_empty() && text.len() + paragraph.len() > NOVEL_PAGE_LENGTH {
+			pages.push(Page {
+				index: pages.len() as i32,
+				url: String::new(),
+				base64: String::new(),
+				text,
+			});
+			text = String::new();
+		}
+		if !text.is_empty() {
+			text.push_str("\n\n");
+		}
+		text.push_str(paragraph);
+	}
+	if !text.is_empty() {
+		pages.push(Page {
+			index: pages.len() as i32,
+			url: String::new(),
+			base64: String::new(),
+			text,
+		});
+	}
+	pages
+}
+
 pub fn modify_image_request(base_url: String, request: Request) {
 	request.header("Referer", &base_url);
 }
 
 pub fn handle_url(url: String, data: MadaraSiteData) -> Result<DeepLink> {
-	let mut manga_id = String::new();
-	let parse_url = url.as_str().split('/').collect::<Vec<&str>>();
-	if parse_url.len() >= 4 {
-		manga_id.push_str(parse_url[4]);
-	}
+	// www.example.com/{source_path}/manga-id/chapter-id/?style=list
+	let path = url.split(['?', '#']).next().unwrap_or_default();
+	let path = path.split("://").last().unwrap_or(path);
+	let segments = path
+		.split('/')
+		.skip(1)
+		.filter(|s| !s.is_empty())
+		.collect::<Vec<&str>>();
+	let source_path = data
+		.source_path
+		.split('/')
+		.filter(|s| !s.is_empty())
+		.collect::<Vec<&str>>();
+
+	let manga_index = if source_path.is_empty() {
+		Some(0)
+	} else {
+		segments
+			.windows(source_path.len())
+			.position(|window| window == source_path.as_slice())
+			.map(|index| index + source_path.len())
+	};
+	let Some(manga_index) = manga_index.filter(|index| *index < segments.len()) else {
+		return Err(AidokuError {
+			reason: AidokuErrorKind::Unimplemented,
+		});
+	};
+	let manga_id = segments[manga_index].to_string();
+	let chapter_path = segments[manga_index + 1..].join("/");
+
+	let chapter = if chapter_path.is_empty() {
+		None
+	} else {
+		let chapter_id = format!("{}/{}/", manga_id, chapter_path);
+		let chapters = get_chapter_list(manga_id.clone(), data.clone())?;
+		Some(
+			chapters
+				.into_iter()
+				.find(|chapter| {
+					chapter.id.trim_end_matches('/') == chapter_id.trim_end_matches('/')
+				})
+				.unwrap_or(Chapter {
+					id: chapter_id,
+					title: String::new(),
+					volume: -1.0,
+					chapter: -1.0,
+					date_updated: -1.0,
+					scanlator: String::new(),
+					url: url.clone(),
+					lang: data.lang.clone(),
+				}),
+		)
+	};
+
 	Ok(DeepLink {
 		manga: Some(get_manga_details(manga_id, data)?),
-		chapter: None,
+		chapter,
 	})
 }
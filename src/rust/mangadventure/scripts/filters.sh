@@ -6,6 +6,7 @@
 json='[
 	{type: "title"},
 	{type: "author"},
+	{type: "text", name: "Artist"},
 	{
 		type: "select",
 		name: "Status",
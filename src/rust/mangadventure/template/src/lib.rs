@@ -21,24 +21,6 @@ static NSFW_CATEGORIES: [&str; 2] = ["Hentai", "Smut"];
 
 static SUGGESTIVE_CATEGORIES: [&str; 1] = ["Ecchi"];
 
-macro_rules! get_value {
-	($obj:ident, $key:tt, $as:tt) => {
-		$obj.get(stringify!($key)).$as().unwrap()
-	};
-
-	($obj:ident, $key:tt, $as:tt, $or:expr) => {
-		$obj.get(stringify!($key)).$as().unwrap_or($or)
-	};
-}
-
-macro_rules! get_array_as_vec {
-	($obj:ident, $key:tt) => {
-		get_value!($obj, $key, as_array)
-			.map(|it| it.as_string().unwrap().read())
-			.collect::<Vec<String>>()
-	};
-}
-
 macro_rules! push_sort_param {
 	($params:ident, $asc:expr, $name:literal) => {
 		$params.push_encoded("sort", Some(if $asc { $name } else { concat!('-', $name) }))
@@ -56,14 +38,24 @@ fn vec_intersects(a: &[&str], b: &[String]) -> bool {
 }
 
 #[inline]
-fn json_request(url: String) -> ObjectRef {
-	// TODO: handle possible errors
+fn json_request(url: String) -> Result<ObjectRef> {
 	Request::new(url, HttpMethod::Get)
 		.header("User-Agent", USER_AGENT)
-		.json()
-		.unwrap()
+		.json()?
 		.as_object()
-		.unwrap()
+}
+
+#[inline]
+fn get_string(obj: &ObjectRef, key: &str) -> Result<String> {
+	Ok(obj.get(key).as_string()?.read())
+}
+
+#[inline]
+fn get_string_vec(obj: &ObjectRef, key: &str) -> Result<Vec<String>> {
+	obj.get(key)
+		.as_array()?
+		.map(|it| Ok(it.as_string()?.read()))
+		.collect()
 }
 
 pub struct MangAdventure {
@@ -72,34 +64,69 @@ pub struct MangAdventure {
 }
 
 impl MangAdventure {
+	fn parse_manga(&self, obj: &ObjectRef) -> Result<Manga> {
+		let mut url = get_string(obj, "url")?;
+		url.insert_str(0, self.base_url);
+		let id = get_string(obj, "slug")?;
+		let title = get_string(obj, "title")?;
+		let cover = get_string(obj, "cover").unwrap_or_default();
+		Ok(Manga {
+			id,
+			url,
+			title,
+			cover,
+			author: String::new(),
+			artist: String::new(),
+			description: String::new(),
+			categories: Vec::new(),
+			status: MangaStatus::Unknown,
+			nsfw: MangaContentRating::Safe,
+			viewer: MangaViewer::Rtl,
+		})
+	}
+
+	fn parse_chapter(&self, obj: &ObjectRef) -> Result<Chapter> {
+		let mut url = get_string(obj, "url")?;
+		url.insert_str(0, self.base_url);
+		let title = get_string(obj, "title").unwrap_or_default();
+		let chapter = obj.get("number").as_float()? as f32;
+		let volume = obj.get("volume").as_int().unwrap_or(-1) as f32;
+		let scanlator = get_string_vec(obj, "groups").unwrap_or_default().join(", ");
+		let date_updated = get_string(obj, "published")
+			.ok()
+			.and_then(|it| it.parse::<f64>().ok())
+			.map(|it| it / 1e3)
+			.unwrap_or(0.0);
+
+		Ok(Chapter {
+			url,
+			title,
+			volume,
+			chapter,
+			scanlator,
+			date_updated,
+			lang: String::from(self.language),
+			id: format!("{}", obj.get("id").as_int()?),
+		})
+	}
+
 	fn get_manga_page_result(&self, url: String) -> Result<MangaPageResult> {
-		let json = json_request(url);
-		let last = get_value!(json, last, as_bool);
-		let results = get_value!(json, results, as_array);
+		let json = json_request(url)?;
+		let last = json.get("last").as_bool().unwrap_or(true);
+		let results = json.get("results").as_array()?;
 		let mut manga = vec_from_array::<Manga>(&results);
 
 		for result in results {
-			let obj = result.as_object().unwrap();
+			let Ok(obj) = result.as_object() else {
+				continue;
+			};
 			// exclude licensed series ("chapters": null)
-			if obj.get("chapters").is_none() { continue; }
-			let mut url = get_value!(obj, url, as_string).read();
-			url.insert_str(0, self.base_url);
-			let id = get_value!(obj, slug, as_string).read();
-			let title = get_value!(obj, title, as_string).read();
-			let cover = get_value!(obj, cover, as_string).read();
-			manga.push(Manga {
-				id,
-				url,
-				title,
-				cover,
-				author: String::new(),
-				artist: String::new(),
-				description: String::new(),
-				categories: Vec::new(),
-				status: MangaStatus::Unknown,
-				nsfw: MangaContentRating::Safe,
-				viewer: MangaViewer::Rtl,
-			});
+			if obj.get("chapters").is_none() {
+				continue;
+			}
+			if let Ok(item) = self.parse_manga(&obj) {
+				manga.push(item);
+			}
 		}
 
 		Ok(MangaPageResult {
@@ -108,6 +135,53 @@ impl MangAdventure {
 		})
 	}
 
+	/// Lists the series of the latest chapters, most recent first, looking
+	/// up each of them by its slug.
+	fn get_latest_releases(&self, page: i32) -> Result<MangaPageResult> {
+		let json = json_request(format!(
+			"{}/api/v2/chapters?page={}&date_format=timestamp",
+			self.base_url, page
+		))?;
+		let last = json.get("last").as_bool().unwrap_or(true);
+		let results = json.get("results").as_array()?;
+		let mut slugs = vec_from_array::<String>(&results);
+
+		for result in results {
+			let Ok(obj) = result.as_object() else {
+				continue;
+			};
+			// the chapter urls look like /reader/{slug}/{vol}/{num}/
+			let Ok(url) = get_string(&obj, "url") else {
+				continue;
+			};
+			let Some(slug) = url.split('/').nth(2) else {
+				continue;
+			};
+			if !slugs.iter().any(|it| it == slug) {
+				slugs.push(String::from(slug));
+			}
+		}
+
+		let mut releases = Vec::<Manga>::with_capacity(slugs.len());
+		for slug in slugs {
+			let Ok(obj) = json_request(format!("{}/api/v2/series/{}", self.base_url, slug)) else {
+				continue;
+			};
+			// exclude licensed series ("chapters": null)
+			if obj.get("chapters").is_none() {
+				continue;
+			}
+			if let Ok(item) = self.parse_manga(&obj) {
+				releases.push(item);
+			}
+		}
+
+		Ok(MangaPageResult {
+			manga: releases,
+			has_more: !last,
+		})
+	}
+
 	pub fn get_manga_list(&self, filters: Vec<Filter>, page: i32) -> Result<MangaPageResult> {
 		let mut categories = Vec::<String>::new();
 		let mut params = QueryParameters::new();
@@ -120,11 +194,15 @@ impl MangAdventure {
 					}
 				}
 				FilterType::Author => {
-					// TODO: add artist search when supported
 					if let Ok(value) = filter.value.as_string() {
 						params.push(String::from("author"), Some(value.read()));
 					}
 				}
+				FilterType::Text if filter.name == "Artist" => {
+					if let Ok(value) = filter.value.as_string() {
+						params.push(String::from("artist"), Some(value.read()));
+					}
+				}
 				FilterType::Select => match filter.value.as_int().unwrap_or(0) {
 					0 => params.push_encoded("status", Some("any")),
 					1 => params.push_encoded("status", Some("completed")),
@@ -135,8 +213,8 @@ impl MangAdventure {
 				},
 				FilterType::Sort => {
 					if let Ok(value) = filter.value.as_object() {
-						let asc = get_value!(value, ascending, as_bool, false);
-						match get_value!(value, index, as_int, 0) {
+						let asc = value.get("ascending").as_bool().unwrap_or(false);
+						match value.get("index").as_int().unwrap_or(0) {
 							0 => push_sort_param!(params, asc, "title"),
 							1 => push_sort_param!(params, asc, "views"),
 							2 => push_sort_param!(params, asc, "latest_upload"),
@@ -176,6 +254,7 @@ impl MangAdventure {
 		match listing.name.as_str() {
 			"Most Viewed" => url.push_str("&sort=-views"),
 			"Latest Updates" => url.push_str("&sort=-latest_upload"),
+			"Latest Releases" => return self.get_latest_releases(page),
 			_ => url.push_str("&sort=title"),
 		}
 
@@ -183,17 +262,21 @@ impl MangAdventure {
 	}
 
 	pub fn get_manga_details(&self, id: String) -> Result<Manga> {
-		let json = json_request(format!("{}/api/v2/series/{}", self.base_url, id));
-		let mut url = get_value!(json, url, as_string).read();
+		let json = json_request(format!("{}/api/v2/series/{}", self.base_url, id))?;
+		let mut url = get_string(&json, "url")?;
 		url.insert_str(0, self.base_url);
-		let id = get_value!(json, slug, as_string).read();
-		let title = get_value!(json, title, as_string).read();
-		let cover = get_value!(json, cover, as_string).read();
-		let description = get_value!(json, description, as_string).read();
-		let author = get_array_as_vec!(json, authors).join(", ");
-		let artist = get_array_as_vec!(json, artists).join(", ");
-		let categories = get_array_as_vec!(json, categories);
-		let status = match get_value!(json, status, as_string).read().as_str() {
+		let id = get_string(&json, "slug")?;
+		let title = get_string(&json, "title")?;
+		let cover = get_string(&json, "cover").unwrap_or_default();
+		let description = get_string(&json, "description").unwrap_or_default();
+		let author = get_string_vec(&json, "authors")
+			.unwrap_or_default()
+			.join(", ");
+		let artist = get_string_vec(&json, "artists")
+			.unwrap_or_default()
+			.join(", ");
+		let categories = get_string_vec(&json, "categories").unwrap_or_default();
+		let status = match get_string(&json, "status").unwrap_or_default().as_str() {
 			"completed" => MangaStatus::Completed,
 			"ongoing" => MangaStatus::Ongoing,
 			"hiatus" => MangaStatus::Hiatus,
@@ -227,33 +310,17 @@ impl MangAdventure {
 		let json = json_request(format!(
 			"{}/api/v2/series/{}/chapters?date_format=timestamp",
 			self.base_url, id
-		));
-		let results = get_value!(json, results, as_array);
+		))?;
+		let results = json.get("results").as_array()?;
 		let mut chapters = vec_from_array::<Chapter>(&results);
 
 		for result in results {
-			let obj = result.as_object().unwrap();
-			let mut url = get_value!(obj, url, as_string).read();
-			url.insert_str(0, self.base_url);
-			let title = get_value!(obj, title, as_string).read();
-			let chapter = get_value!(obj, number, as_float) as f32;
-			let volume = get_value!(obj, volume, as_int, -1) as f32;
-			let scanlator = get_array_as_vec!(obj, groups).join(", ");
-			let date_updated = get_value!(obj, published, as_string)
-				.read()
-				.parse::<f64>()
-				.unwrap() / 1e3;
-
-			chapters.push(Chapter {
-				url,
-				title,
-				volume,
-				chapter,
-				scanlator,
-				date_updated,
-				lang: String::from(self.language),
-				id: format!("{}", get_value!(obj, id, as_int)),
-			});
+			let Ok(obj) = result.as_object() else {
+				continue;
+			};
+			if let Ok(chapter) = self.parse_chapter(&obj) {
+				chapters.push(chapter);
+			}
 		}
 
 		Ok(chapters)
@@ -263,14 +330,14 @@ impl MangAdventure {
 		let json = json_request(format!(
 			"{}/api/v2/chapters/{}/pages?track=true",
 			self.base_url, id
-		));
-		let results = get_value!(json, results, as_array);
+		))?;
+		let results = json.get("results").as_array()?;
 		let mut pages = vec_from_array::<Page>(&results);
 
 		for result in results {
-			let obj = result.as_object().unwrap();
-			let url = get_value!(obj, image, as_string).read();
-			let index = get_value!(obj, number, as_int) as i32 - 1;
+			let obj = result.as_object()?;
+			let url = get_string(&obj, "image")?;
+			let index = obj.get("number").as_int()? as i32 - 1;
 
 			pages.push(Page {
 				url,
@@ -288,12 +355,20 @@ impl MangAdventure {
 		if let Some(top) = parts.get(3) {
 			if top == &"reader" {
 				if let Some(slug) = parts.get(4) {
-					let manga = self.get_manga_details(String::from(*slug)).ok();
-					return Ok(DeepLink {
-						manga,
-						chapter: None,
-					});
-					// TODO: implement chapter deeplink
+					let slug = String::from(*slug);
+					// /reader/{slug}/{vol}/{num}/
+					let chapter = match (parts.get(5), parts.get(6)) {
+						(Some(vol), Some(num)) if !num.is_empty() => {
+							let path = format!("/reader/{}/{}/{}", slug, vol, num);
+							self.get_chapter_list(slug.clone())
+								.unwrap_or_default()
+								.into_iter()
+								.find(|it| it.url.trim_end_matches('/').ends_with(&path))
+						}
+						_ => None,
+					};
+					let manga = self.get_manga_details(slug).ok();
+					return Ok(DeepLink { manga, chapter });
 				}
 			}
 		}
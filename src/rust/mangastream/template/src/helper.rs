@@ -1,15 +1,19 @@
 use aidoku::{
 	error::{AidokuError, AidokuErrorKind, Result},
 	helpers::substring::Substring,
-	prelude::format,
-	std::{current_date, html::Node},
-	std::{defaults::defaults_get, net::Request},
-	std::{String, StringRef, Vec},
+	prelude::{format, println},
+	std::{current_date, html::Node, json::parse},
+	std::{
+		defaults::{defaults_get, defaults_set},
+		net::Request,
+	},
+	std::{ObjectRef, String, StringRef, Vec},
 	MangaStatus,
 };
-use core::ptr;
+use core::{fmt, ptr};
 
 use crate::template::{MangaStreamSource, USER_AGENT};
+use challenge::{with_cookies, ChallengeError, ChallengeRequest};
 
 extern crate hashbrown;
 use hashbrown::HashMap;
@@ -130,6 +134,39 @@ pub fn get_chapter_number(id: String) -> f32 {
 		.unwrap_or(0.0)
 }
 
+const VOLUME_PREFIXES: [&str; 6] = ["vol", "volume", "volumen", "tome", "tomo", "cilt"];
+
+/// Splits the volume off a chapter title like "Vol. 3 Ch. 12", returning the
+/// volume number (or -1 if there is none) and the rest of the title
+pub fn get_volume_number(title: &str) -> (f32, String) {
+	let mut volume = -1.0;
+	let mut rest = Vec::new();
+	let mut words = title.split_whitespace().peekable();
+
+	while let Some(word) = words.next() {
+		if volume < 0.0 {
+			let lowercase = word.to_lowercase();
+			let prefix = lowercase.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+			if VOLUME_PREFIXES.contains(&prefix) {
+				// the number is either attached ("Vol.3") or the next word
+				let attached = lowercase[prefix.len()..].trim_start_matches('.');
+				if let Ok(number) = attached.parse::<f32>() {
+					volume = number;
+					continue;
+				}
+				if let Some(number) = words.peek().and_then(|next| next.parse::<f32>().ok()) {
+					volume = number;
+					words.next();
+					continue;
+				}
+			}
+		}
+		rest.push(word);
+	}
+
+	(volume, rest.join(" "))
+}
+
 // generates the search, filter and homepage url
 pub fn get_search_url(
 	source: &MangaStreamSource,
@@ -379,36 +416,77 @@ pub fn get_lang_code() -> String {
 	String::new()
 }
 
+const MANGA_POSTIDS_KEY: &str = "mangastream.manga_postids";
+/// Series looked up one by one since the mapping was downloaded, kept apart
+/// so that the whole mapping isn't written again for each of them
+const MANGA_POSTIDS_ADDED_KEY: &str = "mangastream.manga_postids_added";
+
 static mut CACHED_MANGA_URL_TO_POSTID_MAPPING: Option<HashMap<String, String>> = None;
-static mut CACHED_MAPPING_AT: f64 = 0.0;
+
+fn read_manga_postids(object: &ObjectRef, mapping: &mut HashMap<String, String>) {
+	for key in object.keys() {
+		let Ok(url) = key.as_string() else {
+			continue;
+		};
+		let url = url.read();
+		if let Ok(post_id) = object.get(&url).as_string() {
+			mapping.insert(url, post_id.read());
+		}
+	}
+}
+
+/// Load the manga url to postid mapping persisted in the defaults
+fn load_manga_url_to_postid_mapping() -> Option<HashMap<String, String>> {
+	let object = defaults_get(MANGA_POSTIDS_KEY).ok()?.as_object().ok()?;
+
+	let mut mapping = HashMap::new();
+	read_manga_postids(&object, &mut mapping);
+	if let Ok(added) = defaults_get(MANGA_POSTIDS_ADDED_KEY).and_then(|value| value.as_object()) {
+		read_manga_postids(&added, &mut mapping);
+	}
+
+	Some(mapping)
+}
+
+/// Persist the whole manga url to postid mapping in the defaults
+fn save_manga_url_to_postid_mapping(mapping: &HashMap<String, String>) {
+	let mut object = ObjectRef::new();
+	for (url, post_id) in mapping {
+		object.set(url, StringRef::from(post_id).0);
+	}
+	defaults_set(MANGA_POSTIDS_KEY, object.0);
+	defaults_set(MANGA_POSTIDS_ADDED_KEY, ObjectRef::new().0);
+}
+
+/// Add a single entry to the series looked up since the mapping was
+/// downloaded
+fn save_manga_postid(url: &str, post_id: &str) {
+	let mut object = defaults_get(MANGA_POSTIDS_ADDED_KEY)
+		.and_then(|value| value.as_object())
+		.unwrap_or_default();
+	object.set(url, StringRef::from(post_id).0);
+	defaults_set(MANGA_POSTIDS_ADDED_KEY, object.0);
+}
 
 // This requests the "all manga" listing page in text mode and parses out
-// the postid and url for each manga, and caches it in a hashmap to prevent
-// having to request the page again.
+// the postid and url for each manga.
 //
 // The all manga listing page is the only reliable way to get the postids for
 // each manga, without making a request to each and every manga page when
-// browsing (*cough* paperback *cough*)
+// browsing (*cough* paperback *cough*). As it can be several megabytes on big
+// sites, the mapping is persisted and only downloaded when there is none.
+// Postids don't change, so it's kept up to date one series at a time: new or
+// renamed series are looked up from their page, and opening a series records
+// its current url.
 //
 /// Generate a hashmap of manga url to postid mappings
-fn generate_manga_url_to_postid_mapping(
-	url: &str,
-	pathname: &str,
-) -> Result<HashMap<String, String>> {
-	unsafe {
-		// if the mapping was generated less than 10 minutes ago, use the cached mapping
-		if current_date() - CACHED_MAPPING_AT < 600.0 {
-			if let Some(mapping) = &mut *ptr::addr_of_mut!(CACHED_MANGA_URL_TO_POSTID_MAPPING) {
-				return Ok(mapping.clone());
-			}
-		}
-	}
-
+fn generate_manga_url_to_postid_mapping(url: &str, pathname: &str) -> Result<()> {
 	let all_manga_listing_url = format!("{}/{}/list-mode", url, pathname);
 
-	let html = Request::get(all_manga_listing_url)
+	let html = ChallengeRequest::get(all_manga_listing_url)
 		.header("User-Agent", USER_AGENT)
-		.html()?;
+		.html()
+		.map_err(ChallengeError::report)?;
 	let mut mapping = HashMap::new();
 
 	for node in html.select(".soralist .series").array() {
@@ -420,22 +498,236 @@ fn generate_manga_url_to_postid_mapping(
 		mapping.insert(url, post_id);
 	}
 
+	save_manga_url_to_postid_mapping(&mapping);
 	unsafe {
-		CACHED_MANGA_URL_TO_POSTID_MAPPING = Some(mapping.clone());
-		CACHED_MAPPING_AT = current_date();
+		CACHED_MANGA_URL_TO_POSTID_MAPPING = Some(mapping);
 	}
 
-	Ok(mapping)
+	Ok(())
 }
 
-/// Search the `MANGA_URL_TO_POSTID_MAPPING` for the postid from a manga url
+/// Make sure the in-memory mapping is loaded, only downloading it if there
+/// is no persisted copy
+fn ensure_manga_url_to_postid_mapping(url: &str, pathname: &str) -> Result<()> {
+	unsafe {
+		if (*ptr::addr_of!(CACHED_MANGA_URL_TO_POSTID_MAPPING)).is_some() {
+			return Ok(());
+		}
+		if let Some(mapping) = load_manga_url_to_postid_mapping() {
+			CACHED_MANGA_URL_TO_POSTID_MAPPING = Some(mapping);
+			return Ok(());
+		}
+	}
+
+	generate_manga_url_to_postid_mapping(url, pathname)
+}
+
+/// Record the current url of the series with `post_id`, read from its page,
+/// so that the mapping follows renamed series
+pub fn record_manga_postid(url: String, post_id: &str) {
+	let Some(mapping) =
+		(unsafe { (*ptr::addr_of_mut!(CACHED_MANGA_URL_TO_POSTID_MAPPING)).as_mut() })
+	else {
+		return;
+	};
+	if url.is_empty() || mapping.get(&url).is_some_and(|id| id == post_id) {
+		return;
+	}
+	save_manga_postid(&url, post_id);
+	mapping.insert(url, String::from(post_id));
+}
+
+/// Read the postid of a series or chapter page from its shortlink or its
+/// article id
+pub fn get_postid_from_html(html: &Node) -> Result<String> {
+	let shortlink = html.select("link[rel=shortlink]").attr("href").read();
+	if shortlink.contains("p=") {
+		return Ok(get_id_from_url(shortlink));
+	}
+
+	let article_id = html.select("article[id^=post-]").first().attr("id").read();
+	match article_id.strip_prefix("post-") {
+		Some(post_id) if !post_id.is_empty() => Ok(String::from(post_id)),
+		_ => Err(AidokuError {
+			reason: AidokuErrorKind::Unimplemented, // no better error type available
+		}),
+	}
+}
+
+/// Request a single series page and read its postid
+fn get_postid_from_manga_page(url: &str) -> Result<String> {
+	let html = ChallengeRequest::get(url)
+		.header("User-Agent", USER_AGENT)
+		.html()
+		.map_err(ChallengeError::report)?;
+
+	let bookmark_id = html
+		.select(".bookmark[data-id]")
+		.first()
+		.attr("data-id")
+		.read();
+	if !bookmark_id.is_empty() {
+		return Ok(bookmark_id);
+	}
+
+	get_postid_from_html(&html)
+}
+
+/// Search the cached manga url to postid mapping for the postid from a manga
+/// url, looking up the series page when it isn't in there yet
 pub fn get_postid_from_manga_url(url: String, base_url: &str, pathname: &str) -> Result<String> {
-	let manga_url_to_postid_mapping = generate_manga_url_to_postid_mapping(base_url, pathname)?;
-	let id = manga_url_to_postid_mapping.get(&url).ok_or(AidokuError {
-		reason: AidokuErrorKind::Unimplemented, // no better error type available
-	})?;
+	ensure_manga_url_to_postid_mapping(base_url, pathname)?;
+
+	let mapping = unsafe {
+		(*ptr::addr_of_mut!(CACHED_MANGA_URL_TO_POSTID_MAPPING)).get_or_insert_with(HashMap::new)
+	};
+	if let Some(id) = mapping.get(&url) {
+		return Ok(id.clone());
+	}
+
+	let id = get_postid_from_manga_page(&url)?;
+	save_manga_postid(&url, &id);
+	mapping.insert(url, id.clone());
+
+	Ok(id)
+}
+
+/// Delay, in seconds, before retrying a rate limited admin ajax request when
+/// the server doesn't say
+const AJAX_DEFAULT_RETRY_AFTER: f64 = 10.0;
+/// Rate limited admin ajax requests are retried at most this many times
+const AJAX_MAX_RETRIES: i32 = 2;
+/// Longest `Retry-After` delay waited for before retrying, in seconds
+const AJAX_MAX_RETRY_WAIT: f64 = 10.0;
+
+static mut AJAX_RATE_LIMITED_UNTIL: f64 = 0.0;
+
+/// Errors of an admin ajax request
+#[derive(Debug)]
+pub enum AjaxError {
+	/// The endpoint is rate limiting us for the given number of seconds
+	RateLimited(f64),
+	Request(AidokuError),
+}
+
+impl fmt::Display for AjaxError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::RateLimited(retry_after) => write!(
+				f,
+				"rate limited by admin-ajax, retry in {} seconds",
+				*retry_after as i32
+			),
+			Self::Request(error) => write!(f, "request failed: {:?}", error.reason),
+		}
+	}
+}
+
+impl From<AidokuError> for AjaxError {
+	fn from(error: AidokuError) -> Self {
+		Self::Request(error)
+	}
+}
+
+impl From<AjaxError> for AidokuError {
+	fn from(error: AjaxError) -> Self {
+		match error {
+			AjaxError::Request(error) => error,
+			AjaxError::RateLimited(_) => AidokuError {
+				reason: AidokuErrorKind::Unimplemented, // no better error type available
+			},
+		}
+	}
+}
+
+/// Reads the `Retry-After` header, which is either a delay in seconds or a
+/// http date, and returns the number of seconds to wait
+fn get_retry_after(request: &Request) -> f64 {
+	let retry_after = request.get_header("Retry-After").and_then(|header| {
+		let value = header.read();
+		let value = value.trim();
+		value.parse::<f64>().ok().or_else(|| {
+			let date = StringRef::from(value).as_date(
+				"EEE, dd MMM yyyy HH:mm:ss zzz",
+				Some("en_US"),
+				None,
+			);
+			if date > 0.0 {
+				Some(date - current_date())
+			} else {
+				None
+			}
+		})
+	});
+	retry_after.unwrap_or(AJAX_DEFAULT_RETRY_AFTER).max(0.0)
+}
+
+/// Remembers that the admin ajax endpoint is rate limiting us for
+/// `retry_after` seconds
+fn rate_limited(retry_after: f64) -> AjaxError {
+	unsafe {
+		AJAX_RATE_LIMITED_UNTIL = current_date() + retry_after;
+	}
+	AjaxError::RateLimited(retry_after)
+}
+
+/// Sends `body` to `ajax_url` once
+fn send_admin_ajax_request(
+	base_url: &str,
+	ajax_url: &str,
+	body: &str,
+) -> core::result::Result<Node, AjaxError> {
+	let request = with_cookies(
+		Request::post(ajax_url)
+			.body(body.as_bytes())
+			.header("Referer", base_url)
+			.header("User-Agent", USER_AGENT),
+		ajax_url,
+	);
+	request.send();
+	let status_code = request.status_code();
+	let retry_after = get_retry_after(&request);
+	if status_code == 429 || status_code == 503 {
+		return Err(AjaxError::RateLimited(retry_after));
+	}
+
+	let html = request.html()?;
+	// some sites answer with a rate limit page instead of the status code
+	if html.select("title").text().read() == "429 Too Many Requests" {
+		return Err(AjaxError::RateLimited(retry_after));
+	}
+	Ok(html)
+}
+
+/// Posts `body` to the admin ajax endpoint of `base_url`
+///
+/// A "429 Too Many Requests" or "503 Service Unavailable" response is retried
+/// up to [`AJAX_MAX_RETRIES`] times after its `Retry-After` delay, unless the
+/// delay is longer than [`AJAX_MAX_RETRY_WAIT`]. Then
+/// [`AjaxError::RateLimited`] is returned, and further requests fail without
+/// being sent until the delay has passed.
+pub fn admin_ajax_request(base_url: &str, body: &str) -> core::result::Result<Node, AjaxError> {
+	let wait = unsafe { AJAX_RATE_LIMITED_UNTIL } - current_date();
+	if wait > 0.0 {
+		return Err(AjaxError::RateLimited(wait));
+	}
 
-	Ok(String::from(id))
+	let ajax_url = format!("{}/wp-admin/admin-ajax.php", base_url);
+	let mut retries = 0;
+	loop {
+		match send_admin_ajax_request(base_url, &ajax_url, body) {
+			Err(AjaxError::RateLimited(retry_after))
+				if retries < AJAX_MAX_RETRIES && retry_after <= AJAX_MAX_RETRY_WAIT =>
+			{
+				// there's no way to sleep, so wait the delay out
+				let now = current_date();
+				while current_date() - now < retry_after {}
+				retries += 1;
+			}
+			Err(AjaxError::RateLimited(retry_after)) => return Err(rate_limited(retry_after)),
+			result => return result,
+		}
+	}
 }
 
 // This requests the chapters via the admin ajax endpoint using post ids and
@@ -446,27 +738,12 @@ pub fn generate_chapter_url_to_postid_mapping(
 	post_id: String,
 	base_url: &str,
 ) -> Result<HashMap<String, String>> {
-	let ajax_url = format!("{}/wp-admin/admin-ajax.php", base_url);
-
-	let start = current_date();
-
 	let body = format!("action=get_chapters&id={}", post_id);
-	let html = Request::post(ajax_url)
-		.body(body.as_bytes())
-		.header("Referer", base_url)
-		.header("User-Agent", USER_AGENT)
-		.html()?;
-
-	// Janky retry logic to bypass rate limiting
-	// Retry after 10 seconds if we get rate limited. 10 seconds is the shortest
-	// interval that we can retry without getting rate limited again.
-	if html.select("title").text().read() == "429 Too Many Requests" {
-		loop {
-			if start + 10.0 < current_date() {
-				return generate_chapter_url_to_postid_mapping(post_id, base_url);
-			}
-		}
-	}
+	let html = admin_ajax_request(base_url, &body).map_err(|error| {
+		// errors carry no message, so the reason is left in the app's logs
+		println!("{}", error);
+		AidokuError::from(error)
+	})?;
 
 	let mut mapping = HashMap::new();
 
@@ -481,3 +758,69 @@ pub fn generate_chapter_url_to_postid_mapping(
 
 	Ok(mapping)
 }
+
+/// Returns the json object passed to `ts_reader.run(...)` in the reader scripts
+fn get_ts_reader_json(script: &str) -> Option<&str> {
+	let start = script.find("ts_reader.run(")?;
+	let start = start + script[start..].find('{')?;
+
+	let mut depth = 0;
+	let mut in_string = false;
+	let mut escaped = false;
+	for (at, char) in script[start..].char_indices() {
+		if in_string {
+			match char {
+				_ if escaped => escaped = false,
+				'\\' => escaped = true,
+				'"' => in_string = false,
+				_ => {}
+			}
+			continue;
+		}
+		match char {
+			'"' => in_string = true,
+			'{' => depth += 1,
+			'}' => {
+				depth -= 1;
+				if depth == 0 {
+					return Some(&script[start..=start + at]);
+				}
+			}
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Parses the image list of every server in the `sources` of the ts reader
+pub fn get_ts_reader_servers(html: &Node) -> Result<Vec<Vec<String>>> {
+	let script = html.select("script").html().read();
+	let json = get_ts_reader_json(&script).ok_or(AidokuError {
+		reason: AidokuErrorKind::JsonParseError,
+	})?;
+	let reader = parse(json.as_bytes())?.as_object()?;
+
+	let mut servers = Vec::new();
+	for source in reader.get("sources").as_array()? {
+		let images = source
+			.as_object()?
+			.get("images")
+			.as_array()?
+			.filter_map(|image| image.as_string().ok())
+			.map(|image| image.read())
+			.filter(|image| !image.is_empty())
+			.collect::<Vec<String>>();
+		servers.push(images);
+	}
+	Ok(servers)
+}
+
+/// Index of the reader server picked in the settings, the first one by
+/// default, or the last of the `count` servers if the site has fewer
+pub fn get_preferred_server(count: usize) -> usize {
+	defaults_get("preferredServer")
+		.and_then(|value| value.as_string())
+		.map(|value| value.read().parse::<usize>().unwrap_or(0))
+		.unwrap_or(0)
+		.min(count.saturating_sub(1))
+}
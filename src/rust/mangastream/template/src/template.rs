@@ -1,17 +1,25 @@
 use aidoku::{
 	error::{AidokuError, AidokuErrorKind, Result},
 	prelude::format,
-	std::json::parse,
 	std::net::{HttpMethod, Request},
-	std::{String, Vec},
+	std::{current_date, String, Vec},
 	Chapter, DeepLink, Filter, FilterType, Manga, MangaContentRating, MangaPageResult, MangaViewer,
 	Page,
 };
+use core::ptr;
 
 use crate::helper::*;
+use challenge::{ChallengeError, ChallengeRequest};
 
 pub const USER_AGENT: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) GSA/300.0.598994205 Mobile/15E148 Safari/604";
 
+/// Seconds after which a reader image host is probed again
+const IMAGE_HOST_PROBE_TTL: f64 = 10.0 * 60.0;
+
+/// Reader image hosts probed so far, whether they could be reached and when
+/// they were probed
+static mut PROBED_IMAGE_HOSTS: Vec<(String, bool, f64)> = Vec::new();
+
 pub struct MangaStreamSource {
 	/// Use static post ids instead of dynamic ids parsed from urls \
 	/// Cannot be used together with `has_permanent_manga_url` or
@@ -52,6 +60,8 @@ pub struct MangaStreamSource {
 	pub chapter_selector: &'static str,
 	pub chapter_title: &'static str,
 	pub chapter_date: &'static str,
+	/// Selector for the scanlator of a chapter, relative to `chapter_selector`
+	pub chapter_scanlator: Option<&'static str>,
 	pub chapter_url: &'static str,
 	pub chapter_date_format: &'static str,
 	pub chapter_date_format_2: &'static str,
@@ -106,6 +116,7 @@ impl Default for MangaStreamSource {
 			chapter_selector: "#chapterlist li",
 			chapter_title: "span.chapternum",
 			chapter_date: "span.chapterdate",
+			chapter_scanlator: None,
 			chapter_url: "a",
 			date_string: "NNNN",
 			chapter_date_format : "MMM dd, yyyy",
@@ -195,9 +206,10 @@ impl MangaStreamSource {
 			base_url
 		};
 		let mut mangas: Vec<Manga> = Vec::new();
-		let html = Request::new(url, HttpMethod::Get)
+		let html = ChallengeRequest::get(url)
 			.header("User-Agent", USER_AGENT)
-			.html()?;
+			.html()
+			.map_err(ChallengeError::report)?;
 		for manga in html.select(self.manga_selector).array() {
 			let manga_node = manga.as_node().expect("Failed to get manga as node");
 			let title = manga_node.select(self.manga_title).attr("title").read();
@@ -209,30 +221,7 @@ impl MangaStreamSource {
 				continue;
 			}
 
-			let url: String;
-			let id: String;
-
-			if self.use_manga_postids {
-				let original_url = manga_node.select("a").attr("href").read();
-				id = get_postid_from_manga_url(
-					original_url,
-					&self.base_url,
-					self.traverse_pathname,
-				)?;
-				url = format!("{}/{}/?p={}", self.base_url, self.traverse_pathname, id);
-			} else {
-				url = {
-					let original_url = manga_node.select("a").attr("href").read();
-
-					if self.has_permanent_manga_url {
-						get_permanet_url(original_url)
-					} else {
-						original_url
-					}
-				};
-
-				id = get_id_from_url(url.clone());
-			}
+			let (id, url) = self.get_manga_id(manga_node.select("a").attr("href").read())?;
 
 			let cover = get_image_src(manga_node);
 
@@ -257,6 +246,23 @@ impl MangaStreamSource {
 		})
 	}
 
+	/// Returns the id and the url to use for a series from its original url
+	fn get_manga_id(&self, original_url: String) -> Result<(String, String)> {
+		if self.use_manga_postids {
+			let id =
+				get_postid_from_manga_url(original_url, &self.base_url, self.traverse_pathname)?;
+			let url = format!("{}/{}/?p={}", self.base_url, self.traverse_pathname, id);
+			Ok((id, url))
+		} else {
+			let url = if self.has_permanent_manga_url {
+				get_permanet_url(original_url)
+			} else {
+				original_url
+			};
+			Ok((get_id_from_url(url.clone()), url))
+		}
+	}
+
 	// parse manga details page
 	pub fn parse_manga_details(&self, id: String) -> Result<Manga> {
 		let url = if self.use_manga_postids {
@@ -264,9 +270,14 @@ impl MangaStreamSource {
 		} else {
 			format!("{}/{}/{}", self.base_url, self.traverse_pathname, id)
 		};
-		let html = Request::new(&url, HttpMethod::Get)
+		let html = ChallengeRequest::get(&url)
 			.header("User-Agent", USER_AGENT)
-			.html()?;
+			.html()
+			.map_err(ChallengeError::report)?;
+		if self.use_manga_postids {
+			let canonical_url = html.select("link[rel=canonical]").attr("href").read();
+			record_manga_postid(canonical_url, &id);
+		}
 		let mut title = html.select(self.manga_details_title).text().read();
 		for i in self.manga_title_trim.iter() {
 			if title.contains(i) {
@@ -358,19 +369,24 @@ impl MangaStreamSource {
 		};
 
 		let mut chapters: Vec<Chapter> = Vec::new();
-		let html = Request::new(url, HttpMethod::Get)
+		let html = ChallengeRequest::get(url)
 			.header("User-Agent", USER_AGENT)
-			.html()?;
+			.html()
+			.map_err(ChallengeError::report)?;
 		for chapter in html.select(self.chapter_selector).array() {
 			let chapter_node = chapter.as_node().expect("Failed to get chapter as node");
-			let raw_title = chapter_node.select(self.chapter_title).text().read();
+			let (volume, raw_title) =
+				get_volume_number(&chapter_node.select(self.chapter_title).text().read());
 			let title = {
 				// Because every mangastream source likes to be different and not have a
 				// consistent chapter naming scheme we have to do some hacky stuff to get the
 				// chapter title because we can't use regex
 
 				let mut title = raw_title.split_whitespace().collect::<Vec<&str>>();
-				if title.len() >= 2 && title[0] == "Chapter" && title[1].parse::<f64>().is_ok() {
+				if title.len() >= 2
+					&& ["Chapter", "Ch.", "Ch"].contains(&title[0])
+					&& title[1].parse::<f64>().is_ok()
+				{
 					title.remove(0);
 					title.remove(0);
 				}
@@ -412,15 +428,20 @@ impl MangaStreamSource {
 
 			let chapter_number = get_chapter_number(raw_title.clone());
 			let date_updated = get_date(self, chapter_node.select(self.chapter_date).text());
+			let scanlator = match self.chapter_scanlator {
+				Some(selector) => String::from(chapter_node.select(selector).text().read().trim()),
+				None => String::new(),
+			};
 
 			chapters.push(Chapter {
 				id: chapter_id,
 				title,
+				volume,
 				chapter: chapter_number,
 				date_updated,
+				scanlator,
 				url: chapter_url,
 				lang: String::from(self.language),
-				..Default::default()
 			});
 		}
 		Ok(chapters)
@@ -437,39 +458,18 @@ impl MangaStreamSource {
 		};
 
 		let mut pages: Vec<Page> = Vec::new();
-		let html = Request::new(url, HttpMethod::Get)
+		let html = ChallengeRequest::get(url)
 			.header("Referer", &self.base_url)
 			.header("User-Agent", USER_AGENT)
-			.html()?;
+			.html()
+			.map_err(ChallengeError::report)?;
 		if self.alt_pages {
-			let raw_text = html.select("script").html().read();
-
-			let trimmed_json = {
-				let mut trimmed_json = &raw_text[raw_text.find(r#":[{"s"#).unwrap_or(0) + 2
-					..raw_text
-						.rfind("}]});")
-						.unwrap_or(raw_text.rfind("}],").unwrap_or(0))
-						+ 1];
-
-				if trimmed_json.contains("Default 2") {
-					trimmed_json = &trimmed_json[..trimmed_json.rfind(r#",{"s"#).unwrap_or(0)];
-				}
-
-				// if "post_id" is present, we've gone too far
-				if trimmed_json.contains("post_id") {
-					trimmed_json = &trimmed_json[..trimmed_json.find("}],").unwrap_or(0) + 1];
-				}
-
-				trimmed_json
-			};
-
-			let json = parse(trimmed_json.as_bytes())?.as_object()?;
-			let images = json.get("images").as_array()?;
-			for (index, page) in images.enumerate() {
-				let page_url = urlencode(page.as_string()?.read());
+			let servers = get_ts_reader_servers(&html)?;
+			let images = self.select_ts_reader_server(servers);
+			for (index, page) in images.into_iter().enumerate() {
 				pages.push(Page {
 					index: index as i32,
-					url: page_url,
+					url: page,
 					..Default::default()
 				});
 			}
@@ -496,6 +496,53 @@ impl MangaStreamSource {
 		}
 	}
 
+	/// Pick the image list of the preferred reader server, falling back to the
+	/// next servers when its images can't be loaded
+	fn select_ts_reader_server(&self, servers: Vec<Vec<String>>) -> Vec<String> {
+		let preferred = get_preferred_server(servers.len());
+		let mut order = (0..servers.len()).collect::<Vec<usize>>();
+		if preferred < servers.len() {
+			order.remove(preferred);
+			order.insert(0, preferred);
+		}
+
+		let candidates = order
+			.into_iter()
+			.map(|index| &servers[index])
+			.filter(|images| !images.is_empty())
+			.collect::<Vec<&Vec<String>>>();
+
+		for (at, images) in candidates.iter().enumerate() {
+			// only probe the cdn when there's another server to fall back to
+			if at + 1 == candidates.len() || self.is_image_reachable(&images[0]) {
+				return images.iter().map(urlencode).collect();
+			}
+		}
+		Vec::new()
+	}
+
+	/// Whether the host of `url` serves images, probed with the first byte
+	/// of the image as some CDNs reject HEAD requests. The result is reused
+	/// for `IMAGE_HOST_PROBE_TTL` seconds.
+	fn is_image_reachable(&self, url: &str) -> bool {
+		let host = url.split('/').nth(2).unwrap_or_default();
+		let probed_hosts = unsafe { &mut *ptr::addr_of_mut!(PROBED_IMAGE_HOSTS) };
+		let now = current_date();
+		probed_hosts.retain(|(_, _, probed_at)| now - probed_at < IMAGE_HOST_PROBE_TTL);
+		if let Some((_, reachable, _)) = probed_hosts.iter().find(|(probed, _, _)| probed == host) {
+			return *reachable;
+		}
+
+		let request = Request::new(urlencode(url), HttpMethod::Get)
+			.header("Referer", &self.base_url)
+			.header("User-Agent", USER_AGENT)
+			.header("Range", "bytes=0-0");
+		request.send();
+		let reachable = matches!(request.status_code(), 200 | 206);
+		probed_hosts.push((String::from(host), reachable, now));
+		reachable
+	}
+
 	pub fn modify_image_request(&self, request: Request) {
 		request
 			.header(
@@ -507,10 +554,69 @@ impl MangaStreamSource {
 	}
 
 	pub fn handle_url(&self, url: String) -> Result<DeepLink> {
-		let id = get_id_from_url(url);
+		// series urls are under the traverse pathname, chapter urls are either
+		// `/{series}-chapter-N/` or `/?p={postid}`
+		if url.contains(&format!("/{}/", self.traverse_pathname)) {
+			let id = get_id_from_url(url);
+			return Ok(DeepLink {
+				manga: Self::parse_manga_details(self, id).ok(),
+				chapter: None,
+			});
+		}
+
+		let html = ChallengeRequest::get(&url)
+			.header("User-Agent", USER_AGENT)
+			.html()
+			.map_err(ChallengeError::report)?;
+		let manga_url = {
+			let all_chapters = html.select(".allc a").first().attr("href").read();
+			if all_chapters.is_empty() {
+				html.select(".ts-breadcrumb li:nth-child(2) a")
+					.attr("href")
+					.read()
+			} else {
+				all_chapters
+			}
+		};
+		if manga_url.is_empty() {
+			return Err(AidokuError {
+				reason: AidokuErrorKind::Unimplemented, // no better error type available
+			});
+		}
+
+		let (manga_id, _) = self.get_manga_id(manga_url)?;
+		let chapter_id = if self.use_chapter_postids {
+			get_postid_from_html(&html)?
+		} else if self.has_permanent_chapter_url {
+			get_id_from_url(get_permanet_url(url.clone()))
+		} else {
+			get_id_from_url(url.clone())
+		};
+
+		let chapter = self
+			.parse_chapter_list(manga_id.clone())
+			.ok()
+			.and_then(|chapters| {
+				chapters
+					.into_iter()
+					.find(|chapter| chapter.id == chapter_id)
+			})
+			.unwrap_or_else(|| {
+				let (volume, title) =
+					get_volume_number(&html.select("h1.entry-title").text().read());
+				Chapter {
+					id: chapter_id,
+					volume,
+					chapter: get_chapter_number(title),
+					url,
+					lang: String::from(self.language),
+					..Default::default()
+				}
+			});
+
 		Ok(DeepLink {
-			manga: Self::parse_manga_details(self, id).ok(),
-			chapter: None,
+			manga: Self::parse_manga_details(self, manga_id).ok(),
+			chapter: Some(chapter),
 		})
 	}
 }
@@ -13,6 +13,9 @@ lazy_static! {
 		base_url: "http://animaregia.net",
 		lang: "pt-BR",
 		category: "Categoria",
+		artist: "Artista",
+		status: "Status",
+		release_year: "Ano de lançamento",
 		..Default::default()
 	};
 }
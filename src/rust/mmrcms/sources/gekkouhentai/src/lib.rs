@@ -13,6 +13,9 @@ lazy_static! {
 		base_url: "https://hentai.gekkouscans.com.br",
 		lang: "pt-BR",
 		category: "Categoria",
+		artist: "Artista",
+		status: "Status",
+		release_year: "Ano de lançamento",
 		category_parser: |_, categories| {
 			let mut viewer = MangaViewer::Rtl;
 			for category in categories {
@@ -16,6 +16,9 @@ lazy_static! {
 
 		category: "الفئة",
 		tags: "العلامات",
+		artist: "الرسام",
+		status: "الحالة",
+		release_year: "سنة الإصدار",
 		category_parser: |_, categories| {
 			let mut nsfw = MangaContentRating::Safe;
 			let mut viewer = MangaViewer::Rtl;
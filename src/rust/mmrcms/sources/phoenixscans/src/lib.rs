@@ -8,6 +8,9 @@ mmrcms! {
 		lang: "pl",
 		category: "Kategorii",
 		tags: "Tagów",
+		artist: "Artysta",
+		status: "Status",
+		release_year: "Rok wydania",
 		category_mapper: |idx| {
 			match idx {
 				0 => String::new(),
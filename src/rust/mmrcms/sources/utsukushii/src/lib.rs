@@ -6,6 +6,9 @@ mmrcms! {
 		base_url: "https://manga.utsukushii-bg.com",
 		lang: "bg",
 		category: "Жанр",
+		artist: "Художник",
+		status: "Статус",
+		release_year: "Година на издаване",
 		category_mapper: |idx| {
 			match idx {
 				0 => String::new(),
@@ -17,6 +17,9 @@ use crate::helper::{append_protocol, extract_f32_from_string};
 pub static mut CACHED_MANGA: Option<Node> = None;
 static mut CACHED_MANGA_ID: Option<String> = None;
 
+/// Same as `INTERNAL_USE_SEARCH_ENGINE`, for the advanced search endpoint
+static mut INTERNAL_USE_ADVANCED_SEARCH: bool = true;
+
 /// Internal attribute to control if the source should fall
 /// back to self searching after failing to use the search
 /// engine first time.
@@ -48,12 +51,50 @@ pub struct MMRCMSSource<'a> {
 	/// Localization
 	pub category: &'a str,
 	pub tags: &'a str,
+	pub artist: &'a str,
+	pub status: &'a str,
+	pub release_year: &'a str,
 
 	pub category_parser: fn(&Node, Vec<String>) -> (MangaContentRating, MangaViewer),
 	pub category_mapper: fn(i64) -> String,
 	pub tags_mapper: fn(i64) -> String,
 
 	pub use_search_engine: bool,
+	/// Use `/advSearchFilter` when the title is combined with other filters,
+	/// or when filtering by artist, status or release year
+	pub use_advanced_search: bool,
+}
+
+/// Values of the advanced search form at `/advanced-search`
+#[derive(Default)]
+struct MMRCMSAdvancedSearch {
+	pub name: String,
+	pub author: String,
+	pub artist: String,
+	pub categories: Vec<String>,
+	/// 1 for ongoing, 2 for complete
+	pub status: Option<i64>,
+	pub release: String,
+	pub sort_by: Option<&'static str>,
+	pub asc: bool,
+}
+
+impl MMRCMSAdvancedSearch {
+	/// The form serialized the way the site's javascript posts it
+	fn params(&self) -> String {
+		let mut params = Vec::with_capacity(5 + self.categories.len());
+		params.push(format!("name={}", encode_uri_component(&self.name)));
+		params.push(format!("author={}", encode_uri_component(&self.author)));
+		params.push(format!("artist={}", encode_uri_component(&self.artist)));
+		for category in &self.categories {
+			params.push(format!("categories%5B%5D={}", category));
+		}
+		if let Some(status) = self.status {
+			params.push(format!("status%5B%5D={}", status));
+		}
+		params.push(format!("release={}", encode_uri_component(&self.release)));
+		params.join("&")
+	}
 }
 
 #[derive(Default)]
@@ -81,7 +122,11 @@ impl<'a> Default for MMRCMSSource<'a> {
 			manga_path: "manga",
 
 			category: "Category",
-			tags: "Tag",
+			// the advanced search and the title search have no tags
+			tags: "Tag (ignored with a title, artist, status or year)",
+			artist: "Artist",
+			status: "Status",
+			release_year: "Release year",
 
 			category_parser: |_, categories| {
 				let mut nsfw = MangaContentRating::Safe;
@@ -112,6 +157,7 @@ impl<'a> Default for MMRCMSSource<'a> {
 			}, // 0 is reserved for None
 			tags_mapper: |_| String::new(),
 			use_search_engine: true,
+			use_advanced_search: true,
 		}
 	}
 }
@@ -169,43 +215,169 @@ impl<'a> MMRCMSSource<'a> {
 		})
 	}
 
+	fn parse_manga_node(&self, manga_node: &Node) -> Manga {
+		let url = manga_node
+			.select(format!("a[href*='{}/{}']", self.base_url, self.manga_path))
+			.attr("abs:href")
+			.read();
+		let id = url.replace(&format!("{}/{}/", self.base_url, self.manga_path), "");
+		let cover = self.guess_cover(
+			&manga_node
+				.select(format!(
+					"a[href*='{}/{}'] img",
+					self.base_url, self.manga_path
+				))
+				.attr("abs:src")
+				.read(),
+			&id,
+		);
+		let title = manga_node
+			.select("a.chart-title strong, h5.media-heading a, a.chart-title")
+			.first()
+			.text()
+			.read();
+		Manga {
+			id,
+			cover,
+			title,
+			url,
+			..Default::default()
+		}
+	}
+
+	fn search_suggestions(&self, title: &str) -> Result<MangaPageResult> {
+		if self.use_search_engine && unsafe { INTERNAL_USE_SEARCH_ENGINE } {
+			let url = format!("{}/search?query={}", self.base_url, encode_uri_component(title));
+			if let Ok(obj) = Request::new(&url, HttpMethod::Get).json()
+			   && let Ok(json) = obj.as_object()
+			   && let Ok(suggestions) = json.get("suggestions").as_array() {
+				let mut manga = Vec::with_capacity(suggestions.len());
+				for suggestion in suggestions {
+					if let Ok(suggestion) = suggestion.as_object()
+					   && let Ok(obj) = MMRCMSSearchResult::try_from(suggestion) {
+						manga.push(Manga {
+							cover: self.guess_cover("", &obj.data),
+							url: format!("{}/{}/{}", self.base_url, self.manga_path, obj.data),
+							id: obj.data,
+							title: obj.value,
+							..Default::default()
+						});
+					}
+				}
+				Ok(MangaPageResult {
+					manga,
+					has_more: false,
+				})
+			} else {
+				unsafe { INTERNAL_USE_SEARCH_ENGINE = false };
+				self.self_search(title.to_lowercase())
+			}
+		} else {
+			self.self_search(title.to_lowercase())
+		}
+	}
+
+	/// Returns None if the site has no advanced search, so that the regular
+	/// search is used instead
+	fn advanced_search(
+		&self,
+		search: &MMRCMSAdvancedSearch,
+		page: i32,
+	) -> Result<Option<MangaPageResult>> {
+		let mut body = format!(
+			"params={}&page={}",
+			encode_uri_component(search.params()),
+			itoa::Buffer::new().format(page)
+		);
+		if let Some(sort_by) = search.sort_by {
+			body.push_str(&format!(
+				"&sortBy={}&asc={}",
+				sort_by,
+				if search.asc { "true" } else { "false" }
+			));
+		}
+		let request = Request::new(format!("{}/advSearchFilter", self.base_url), HttpMethod::Post)
+			.header("Content-Type", "application/x-www-form-urlencoded")
+			.header("X-Requested-With", "XMLHttpRequest")
+			.header("Referer", &format!("{}/advanced-search", self.base_url))
+			.body(body.as_bytes());
+		request.send();
+		if matches!(request.status_code(), 404 | 405) {
+			// the site doesn't have the advanced search
+			unsafe { INTERNAL_USE_ADVANCED_SEARCH = false };
+			return Ok(None);
+		}
+		let html = request.html()?;
+		decode_cfemail(&html);
+
+		let manga = html
+			.select("div.media")
+			.array()
+			.filter_map(|elem| elem.as_node().ok())
+			.map(|node| self.parse_manga_node(&node))
+			.filter(|manga| !manga.id.is_empty())
+			.collect::<Vec<_>>();
+		let has_more = !html
+			.select(".pagination li.active + li a")
+			.array()
+			.is_empty();
+
+		Ok(Some(MangaPageResult { manga, has_more }))
+	}
+
 	pub fn get_manga_list(&self, filters: Vec<Filter>, page: i32) -> Result<MangaPageResult> {
 		let mut query: Vec<String> = Vec::new();
-		let mut title = String::new();
+		let mut search = MMRCMSAdvancedSearch::default();
+		// whether the filters can only be handled by the advanced search
+		let mut needs_advanced_search = false;
 		for filter in filters {
 			match filter.kind {
 				FilterType::Title => {
-					let t = filter
+					search.name = filter
+						.value
+						.as_string()
+						.map(|v| v.read())
+						.unwrap_or_default();
+				}
+				FilterType::Author => {
+					search.author = filter
 						.value
 						.as_string()
 						.map(|v| v.read())
 						.unwrap_or_default();
-					if t.is_empty() {
+					if !search.author.is_empty() {
+						query.push(format!("artist={}", encode_uri_component(&search.author)));
+					}
+				}
+				FilterType::Text => {
+					let value = filter
+						.value
+						.as_string()
+						.map(|v| v.read())
+						.unwrap_or_default();
+					if value.is_empty() {
 						continue;
 					}
-					title = encode_uri_component(t);
-					break;
+					match filter.name.as_str() {
+						x if x == self.artist => search.artist = value,
+						x if x == self.release_year => search.release = value,
+						_ => continue,
+					}
+					needs_advanced_search = true;
 				}
-				FilterType::Author => query.push(format!(
-					"artist={}",
-					encode_uri_component(
-						filter
-							.value
-							.as_string()
-							.map(|v| v.read())
-							.unwrap_or_default()
-					)
-				)),
 				FilterType::Sort => {
 					if let Ok(value) = filter.value.as_object() {
 						let index = value.get("index").as_int().unwrap_or(0);
 						let asc = value.get("ascending").as_bool().unwrap_or(false);
-						query.push(String::from(match index {
-							0 => "sortBy=name",
-							1 => "sortBy=views",
-							2 => "sortBy=last_release", // Readcomicsonline.ru
+						let sort_by = match index {
+							0 => "name",
+							1 => "views",
+							2 => "last_release", // Readcomicsonline.ru
 							_ => continue,
-						}));
+						};
+						search.sort_by = Some(sort_by);
+						search.asc = asc;
+						query.push(format!("sortBy={}", sort_by));
 						query.push(format!("asc={}", if asc { "true" } else { "false" }));
 					}
 				}
@@ -213,47 +385,42 @@ impl<'a> MMRCMSSource<'a> {
 					let value = filter.value.as_int().unwrap_or(-1);
 					match filter.name.as_str() {
 						x if x == self.category => {
-							query.push(format!("cat={}", (self.category_mapper)(value)))
+							let category = (self.category_mapper)(value);
+							if !category.is_empty() {
+								search.categories.push(category.clone());
+							}
+							query.push(format!("cat={}", category))
 						}
 						x if x == self.tags => {
 							query.push(format!("tag={}", (self.tags_mapper)(value)))
 						}
+						x if x == self.status => {
+							// 0 is reserved for any status
+							if value > 0 {
+								search.status = Some(value);
+								needs_advanced_search = true;
+							}
+						}
 						_ => continue,
 					}
 				}
 				_ => continue,
 			}
 		}
-		if !title.is_empty() {
-			if self.use_search_engine && unsafe { INTERNAL_USE_SEARCH_ENGINE } {
-				let url = format!("{}/search?query={}", self.base_url, title);
-				if let Ok(obj) = Request::new(&url, HttpMethod::Get).json()
-				   && let Ok(json) = obj.as_object()
-				   && let Ok(suggestions) = json.get("suggestions").as_array() {
-					let mut manga = Vec::with_capacity(suggestions.len());
-					for suggestion in suggestions {
-						if let Ok(suggestion) = suggestion.as_object()
-						   && let Ok(obj) = MMRCMSSearchResult::try_from(suggestion) {
-							manga.push(Manga {
-								cover: self.guess_cover("", &obj.data),
-								url: format!("{}/{}/{}", self.base_url, self.manga_path, obj.data),
-								id: obj.data,
-								title: obj.value,
-								..Default::default()
-							});
-						}
-					}
-					Ok(MangaPageResult {
-						manga,
-						has_more: false,
-					})
-				} else {
-					unsafe { INTERNAL_USE_SEARCH_ENGINE = false };
-					self.self_search(title)
-				}
-			} else {
-				self.self_search(title)
-			}
+
+		let has_filters = needs_advanced_search
+			|| !search.author.is_empty()
+			|| !search.categories.is_empty();
+		if self.use_advanced_search
+			&& unsafe { INTERNAL_USE_ADVANCED_SEARCH }
+			&& (needs_advanced_search || (!search.name.is_empty() && has_filters))
+			&& let Ok(Some(result)) = self.advanced_search(&search, page)
+		{
+			return Ok(result);
+		}
+
+		if !search.name.is_empty() {
+			self.search_suggestions(&search.name)
 		} else {
 			let url = format!(
 				"{}/filterList?page={}&{}",
@@ -270,29 +437,7 @@ impl<'a> MMRCMSSource<'a> {
 
 			for elem in elems {
 				if let Ok(manga_node) = elem.as_node() {
-					let url = manga_node
-						.select(format!("a[href*='{}/{}']", self.base_url, self.manga_path))
-						.attr("abs:href")
-						.read();
-					let id = url.replace(&format!("{}/{}/", self.base_url, self.manga_path), "");
-					let cover = self.guess_cover(
-						&manga_node
-							.select(format!(
-								"a[href*='{}/{}'] img",
-								self.base_url, self.manga_path
-							))
-							.attr("abs:src")
-							.read(),
-						&id,
-					);
-					let title = manga_node.select("a.chart-title strong").text().read();
-					manga.push(Manga {
-						id: id.clone(),
-						cover,
-						title,
-						url,
-						..Default::default()
-					});
+					manga.push(self.parse_manga_node(&manga_node));
 				}
 			}
 			Ok(MangaPageResult { manga, has_more })
@@ -0,0 +1,165 @@
+use aidoku::{
+	prelude::*,
+	std::{
+		defaults::{defaults_get, defaults_set},
+		net::Request,
+		String, StringRef, Vec,
+	},
+};
+use alloc::vec;
+
+use crate::get_base_url;
+
+/// Cookies of the session opened with the username and password.
+const SESSION_KEY: &str = "komga.session";
+
+#[derive(PartialEq, Eq)]
+enum AuthMode {
+	Basic,
+	ApiKey,
+	Session,
+}
+
+fn get_setting(key: &str) -> String {
+	defaults_get(key)
+		.and_then(|v| v.as_string().map(|v| v.read()))
+		.unwrap_or_default()
+}
+
+fn get_auth_mode() -> AuthMode {
+	match get_setting("authMode").as_str() {
+		"apiKey" => AuthMode::ApiKey,
+		"session" => AuthMode::Session,
+		_ => AuthMode::Basic,
+	}
+}
+
+fn get_basic_authorization() -> String {
+	let username = get_setting("username");
+	let password = get_setting("password");
+
+	let auth = format!("{username}:{password}");
+
+	let authb = auth.as_bytes();
+	let mut buf = vec![0; authb.len() * 4 / 3 + 4];
+	let len = base64::encode_config_slice(authb, base64::STANDARD, &mut buf);
+	buf.resize(len, 0);
+
+	format!("Basic {}", String::from_utf8_lossy(&buf))
+}
+
+/// Reads a cookie from a `Set-Cookie` header, which holds every cookie set
+/// by the response separated by commas.
+fn get_cookie<'a>(set_cookie: &'a str, name: &str) -> Option<&'a str> {
+	set_cookie
+		.split([',', ';'])
+		.find_map(|it| it.trim().strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Logs in with the username and password once and remembers the session,
+/// so that the credentials aren't sent with every request.
+fn log_in() -> Option<String> {
+	let base_url = get_base_url().ok()?;
+	let request = Request::get(format!("{base_url}/api/v1/users/me?remember-me=true"))
+		.header("Authorization", &get_basic_authorization());
+	request.send();
+	if request.status_code() != 200 {
+		return None;
+	}
+
+	let mut cookies = Vec::new();
+	if let Some(set_cookie) = request.get_header("Set-Cookie") {
+		let set_cookie = set_cookie.read();
+		for name in ["remember-me", "SESSION"] {
+			if let Some(value) = get_cookie(&set_cookie, name) {
+				cookies.push(format!("{name}={value}"));
+			}
+		}
+	}
+	// the session id is also sent as a header, in case the cookies aren't
+	if cookies.is_empty() {
+		let token = request.get_header("X-Auth-Token")?.read();
+		cookies.push(format!("SESSION={token}"));
+	}
+
+	let session = cookies.join("; ");
+	defaults_set(SESSION_KEY, StringRef::from(&session).0);
+	Some(session)
+}
+
+fn get_session() -> Option<String> {
+	let session = get_setting(SESSION_KEY);
+	if session.is_empty() {
+		log_in()
+	} else {
+		Some(session)
+	}
+}
+
+/// Forgets the stored session, returning whether there was one.
+pub fn clear_session() -> bool {
+	let had_session = !get_setting(SESSION_KEY).is_empty();
+	if had_session {
+		defaults_set(SESSION_KEY, StringRef::from("").0);
+	}
+	had_session
+}
+
+/// Adds the configured authentication to a request, falling back to Basic
+/// authentication when no API key is set or there's no session.
+fn authorize_with(request: Request, session: Option<String>) -> Request {
+	match get_auth_mode() {
+		AuthMode::ApiKey => {
+			let api_key = get_setting("apiKey");
+			if !api_key.is_empty() {
+				return request.header("X-API-Key", &api_key);
+			}
+		}
+		AuthMode::Session => {
+			if let Some(session) = session {
+				return request.header("Cookie", &session);
+			}
+		}
+		AuthMode::Basic => {}
+	}
+	request.header("Authorization", &get_basic_authorization())
+}
+
+fn authorize(request: Request) -> Request {
+	let session = if get_auth_mode() == AuthMode::Session {
+		get_session()
+	} else {
+		None
+	};
+	authorize_with(request, session)
+}
+
+/// Authorizes an image request with the session stored when the page list
+/// was fetched, as logging in for every image would defeat the session.
+pub fn authorize_image(request: Request) -> Request {
+	let session = Some(get_setting(SESSION_KEY)).filter(|v| !v.is_empty());
+	authorize_with(request, session)
+}
+
+/// Sends an authorized request, logging in again once if the stored
+/// session has expired.
+fn send<F: Fn() -> Request>(build: F) -> Vec<u8> {
+	let request = authorize(build());
+	request.send();
+	if request.status_code() == 401 && get_auth_mode() == AuthMode::Session && clear_session() {
+		return authorize(build()).data();
+	}
+	request.data()
+}
+
+pub fn get_data(url: &str) -> Vec<u8> {
+	send(|| Request::get(url))
+}
+
+pub fn post_json(url: &str, body: &[u8]) -> Vec<u8> {
+	send(|| {
+		Request::post(url)
+			.header("Content-Type", "application/json")
+			.body(body)
+	})
+}
@@ -1,6 +1,6 @@
 use aidoku::{
-	std::{String, Vec},
-	Manga, MangaContentRating, MangaStatus,
+	std::{String, StringRef, Vec},
+	Chapter, Manga, MangaContentRating, MangaStatus,
 };
 use alloc::borrow::ToOwned;
 use serde::Deserialize;
@@ -183,6 +183,130 @@ pub struct BookDto<'a> {
 	pub metadata: BookMetadataDto,
 }
 
+impl BookDto<'_> {
+	pub fn to_chapter<T: AsRef<str>>(&self, base_url: T) -> Chapter {
+		let mut date_updated = self
+			.metadata
+			.release_date
+			.as_ref()
+			.map(|v| StringRef::from(v).as_date("yyyy-MM-dd", Some("en_US"), None))
+			.unwrap_or(-1.0);
+		if date_updated == -1.0 {
+			date_updated = StringRef::from(&self.file_last_modified).as_date(
+				"yyyy-MM-dd'T'HH:mm:ss",
+				Some("en_US"),
+				None,
+			);
+		}
+		if date_updated == -1.0 {
+			date_updated = StringRef::from(&self.file_last_modified).as_date(
+				"yyyy-MM-dd'T'HH:mm:ss'Z",
+				Some("en_US"),
+				None,
+			);
+		}
+		if date_updated == -1.0 {
+			date_updated = StringRef::from(&self.file_last_modified).as_date(
+				"yyyy-MM-dd'T'HH:mm:ss.S",
+				Some("en_US"),
+				None,
+			);
+		}
+		Chapter {
+			id: self.id.to_owned(),
+			url: [base_url.as_ref(), "/book/", self.id].concat(),
+			title: self.metadata.title.clone(),
+			chapter: self.metadata.number_sort,
+			date_updated,
+			..Default::default()
+		}
+	}
+}
+
+/// Libraries, collections and read lists are shown as manga whose id is
+/// prefixed with their kind, e.g. `readlist:{id}`.
+#[derive(Default, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LibraryDto<'a> {
+	pub id: &'a str,
+	pub name: String,
+}
+
+impl LibraryDto<'_> {
+	/// The library's series are browsed with the Library filter, so the
+	/// description points to it.
+	pub fn into_manga<T: AsRef<str>>(self, base_url: T) -> Manga {
+		Manga {
+			url: [base_url.as_ref(), "/libraries/", self.id, "/series"].concat(),
+			id: ["library:", self.id].concat(),
+			description: [
+				"Search with the Library filter set to \"",
+				&self.name,
+				"\" to browse its series.",
+			]
+			.concat(),
+			title: self.name,
+			..Default::default()
+		}
+	}
+}
+
+#[derive(Default, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CollectionDto<'a> {
+	pub id: &'a str,
+	pub name: String,
+	pub ordered: bool,
+	#[serde(borrow)]
+	pub series_ids: Vec<&'a str>,
+}
+
+impl CollectionDto<'_> {
+	/// The collection's series are browsed with the Collection filter, so the
+	/// description points to it.
+	pub fn into_manga<T: AsRef<str>>(self, base_url: T) -> Manga {
+		let base_url = base_url.as_ref();
+		Manga {
+			url: [base_url, "/collections/", self.id].concat(),
+			cover: [base_url, "/api/v1/collections/", self.id, "/thumbnail"].concat(),
+			id: ["collection:", self.id].concat(),
+			description: [
+				"Search with the Collection filter set to \"",
+				&self.name,
+				"\" to browse its series.",
+			]
+			.concat(),
+			title: self.name,
+			..Default::default()
+		}
+	}
+}
+
+#[derive(Default, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReadListDto<'a> {
+	pub id: &'a str,
+	pub name: String,
+	pub summary: String,
+	pub ordered: bool,
+	#[serde(borrow)]
+	pub book_ids: Vec<&'a str>,
+}
+
+impl ReadListDto<'_> {
+	pub fn into_manga<T: AsRef<str>>(self, base_url: T) -> Manga {
+		let base_url = base_url.as_ref();
+		Manga {
+			url: [base_url, "/readlists/", self.id].concat(),
+			cover: [base_url, "/api/v1/readlists/", self.id, "/thumbnail"].concat(),
+			id: ["readlist:", self.id].concat(),
+			title: self.name,
+			description: self.summary,
+			..Default::default()
+		}
+	}
+}
+
 #[derive(Default, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase", default)]
 pub struct PageDto<'a> {
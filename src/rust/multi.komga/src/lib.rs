@@ -1,51 +1,93 @@
 #![no_std]
+mod auth;
 mod dto;
+mod search;
 extern crate alloc;
 use aidoku::{
 	error::{AidokuError, AidokuErrorKind, Result},
-	helpers::uri::encode_uri,
+	helpers::uri::{encode_uri, encode_uri_component},
 	prelude::*,
-	std::{defaults::defaults_get, net::Request, String, StringRef, Vec},
-	Chapter, Filter, FilterType, Listing, Manga, MangaPageResult, Page,
+	std::{defaults::defaults_get, net::Request, String, Vec},
+	Chapter, DeepLink, Filter, FilterType, Listing, Manga, MangaPageResult, Page,
 };
-use alloc::{vec, borrow::ToOwned, string::ToString};
-use dto::{BookDto, PageDto, PageWrapperDto, SeriesDto};
-
-fn get_authorization_header() -> String {
-	let username = defaults_get("username")
-		.and_then(|v| v.as_string().map(|v| v.read()))
-		.unwrap_or_default();
-	let password = defaults_get("password")
-		.and_then(|v| v.as_string().map(|v| v.read()))
-		.unwrap_or_default();
+use alloc::{borrow::ToOwned, string::ToString};
+use dto::{
+	AuthorDto, BookDto, CollectionDto, LibraryDto, PageDto, PageWrapperDto, ReadListDto, SeriesDto,
+};
+use search::{SeriesSearch, METADATA_FILTERS};
+use serde_json::json;
 
-	let auth = format!("{username}:{password}");
+pub fn get_base_url() -> Result<String> {
+	defaults_get("baseURL")?
+		.as_string()
+		.map(|v| v.read().trim_end_matches('/').to_string())
+}
 
-	let authb = auth.as_bytes();
-	let mut buf = vec![0; authb.len() * 4 / 3 + 4];
-	let len = base64::encode_config_slice(authb, base64::STANDARD, &mut buf);
-	buf.resize(len, 0);
+/// Returns the id of the library named `value`, or `value` itself if it's
+/// not a library name.
+fn get_library_id(base_url: &str, value: &str) -> String {
+	let data = auth::get_data(&format!("{base_url}/api/v1/libraries"));
+	serde_json::from_slice::<Vec<LibraryDto>>(&data)
+		.ok()
+		.and_then(|v| {
+			v.into_iter()
+				.find(|library| library.id == value || library.name.eq_ignore_ascii_case(value))
+				.map(|library| library.id.to_owned())
+		})
+		.unwrap_or_else(|| value.to_owned())
+}
 
-	format!("Basic {}", String::from_utf8_lossy(&buf))
+/// Returns the id of the collection named `value`, or `value` itself if
+/// it's not a collection name.
+fn get_collection_id(base_url: &str, value: &str) -> String {
+	let url = format!(
+		"{base_url}/api/v1/collections?unpaged=true&search={}",
+		encode_uri_component(value)
+	);
+	let data = auth::get_data(&url);
+	serde_json::from_slice::<PageWrapperDto<CollectionDto>>(&data)
+		.ok()
+		.and_then(|v| {
+			v.content
+				.into_iter()
+				.find(|collection| {
+					collection.id == value || collection.name.eq_ignore_ascii_case(value)
+				})
+				.map(|collection| collection.id.to_owned())
+		})
+		.unwrap_or_else(|| value.to_owned())
 }
 
-fn get_base_url() -> Result<String> {
-	defaults_get("baseURL")?
-		.as_string()
-		.map(|v| v.read().trim_end_matches('/').to_string())
+/// Returns the books at `url` as chapters, in the order of the api.
+fn get_books(base_url: &str, url: String, with_series_title: bool) -> Result<Vec<Chapter>> {
+	let data = auth::get_data(&encode_uri(url));
+	serde_json::from_slice(&data)
+		.map(|v: PageWrapperDto<BookDto>| {
+			v.content
+				.iter()
+				.map(|book| {
+					let mut chapter = book.to_chapter(base_url);
+					if with_series_title {
+						chapter.title = format!("{} - {}", book.series_title, chapter.title);
+					}
+					chapter
+				})
+				.collect::<Vec<_>>()
+		})
+		.map_err(|_| AidokuError {
+			reason: AidokuErrorKind::JsonParseError,
+		})
 }
 
 #[get_manga_list]
 fn get_manga_list(filters: Vec<Filter>, page: i32) -> Result<MangaPageResult> {
 	let base_url = get_base_url()?;
-	let mut url = base_url.clone();
-	url.push_str("/api/v1/series?deleted=false&page=");
-	url.push_str(itoa::Buffer::new().format(page - 1));
+	let mut search = SeriesSearch::default();
 	for filter in filters {
 		match filter.kind {
 			FilterType::Check => {
 				if let Ok(id) = filter.object.get("id").as_string() {
-					url.push_str(&id.read());
+					search.push_filter_id(&id.read());
 				}
 			}
 			FilterType::Sort => {
@@ -58,28 +100,54 @@ fn get_manga_list(filters: Vec<Filter>, page: i32) -> Result<MangaPageResult> {
 						2 => "lastModifiedDate",
 						_ => continue,
 					};
-					url.push_str("&sort=");
-					url.push_str(property);
-					url.push(',');
-					url.push_str(if ascending { "asc" } else { "desc" });
+					search.sort(format!(
+						"{property},{}",
+						if ascending { "asc" } else { "desc" }
+					));
+				}
+			}
+			FilterType::Text => {
+				let value = filter
+					.value
+					.as_string()
+					.map(|v| v.read())
+					.unwrap_or_default();
+				let value = value.trim();
+				if value.is_empty() {
+					continue;
+				}
+				match filter.name.as_str() {
+					"Library" => {
+						let id = get_library_id(&base_url, value);
+						search.include("library_id", &id, "libraryId", Some(json!(id)));
+					}
+					"Collection" => {
+						let id = get_collection_id(&base_url, value);
+						search.include("collection_id", &id, "collectionId", Some(json!(id)));
+					}
+					"Authors" => search.push_authors(&base_url, value),
+					name => {
+						if let Some(metadata) = METADATA_FILTERS.iter().find(|v| v.0 == name) {
+							search.push_metadata(&base_url, *metadata, value);
+						}
+					}
 				}
 			}
 			FilterType::Title => {
 				if let Ok(title) = filter.value.as_string() {
 					let title = title.read();
 					if title.starts_with("regex:") {
-						url.push_str("&search_regex=");
-						url.push_str(title
-								.strip_prefix("regex:")
-								.map(|v| v.trim())
-								.unwrap_or_default(),
-						);
+						let mut regex = title
+							.strip_prefix("regex:")
+							.map(|v| v.trim())
+							.unwrap_or_default()
+							.to_string();
 						if !title.contains(",TITLE") && !title.contains(",TITLE_SORT") {
-							url.push_str(",TITLE");
+							regex.push_str(",TITLE");
 						}
+						search.search_regex(&regex);
 					} else {
-						url.push_str("&search=");
-						url.push_str(&title);
+						search.search(&title);
 					}
 				}
 			}
@@ -87,9 +155,7 @@ fn get_manga_list(filters: Vec<Filter>, page: i32) -> Result<MangaPageResult> {
 		}
 	}
 
-	let data = Request::get(encode_uri(url))
-		.header("Authorization", &get_authorization_header())
-		.data();
+	let data = search.send(&base_url, page);
 	serde_json::from_slice(&data)
 		.map(|v: PageWrapperDto<SeriesDto>| MangaPageResult {
 			manga: v
@@ -104,9 +170,120 @@ fn get_manga_list(filters: Vec<Filter>, page: i32) -> Result<MangaPageResult> {
 		})
 }
 
+fn get_libraries(base_url: &str) -> Result<MangaPageResult> {
+	let data = auth::get_data(&format!("{base_url}/api/v1/libraries"));
+	serde_json::from_slice(&data)
+		.map(|v: Vec<LibraryDto>| MangaPageResult {
+			manga: v
+				.into_iter()
+				.map(|v| v.into_manga(base_url))
+				.collect::<Vec<_>>(),
+			has_more: false,
+		})
+		.map_err(|_| AidokuError {
+			reason: AidokuErrorKind::JsonParseError,
+		})
+}
+
+fn get_collections(base_url: &str, page: i32) -> Result<MangaPageResult> {
+	let url = format!("{base_url}/api/v1/collections?page={}", page - 1);
+	let data = auth::get_data(&url);
+	serde_json::from_slice(&data)
+		.map(|v: PageWrapperDto<CollectionDto>| MangaPageResult {
+			manga: v
+				.content
+				.into_iter()
+				.map(|v| v.into_manga(base_url))
+				.collect::<Vec<_>>(),
+			has_more: !v.last,
+		})
+		.map_err(|_| AidokuError {
+			reason: AidokuErrorKind::JsonParseError,
+		})
+}
+
+/// Returns a value of a metadata filter as a manga, pointing to the filter
+/// that browses its series.
+fn get_value_manga(filter: &str, param: &str, value: String) -> Manga {
+	Manga {
+		id: format!("{param}:{value}"),
+		description: format!(
+			"Search with the {filter} filter set to \"{value}\" to browse its series."
+		),
+		title: value,
+		..Default::default()
+	}
+}
+
+/// Returns the server's values of a metadata filter, or `None` if
+/// `listing` isn't one.
+fn get_metadata_values(base_url: &str, listing: &str) -> Option<MangaPageResult> {
+	let (filter, param, _, endpoint) = METADATA_FILTERS.iter().find(|v| v.0 == listing)?;
+	Some(MangaPageResult {
+		manga: search::get_values(base_url, endpoint)
+			.into_iter()
+			.map(|value| get_value_manga(filter, param, value))
+			.collect::<Vec<_>>(),
+		has_more: false,
+	})
+}
+
+fn get_authors(base_url: &str, page: i32) -> Result<MangaPageResult> {
+	let url = format!("{base_url}/api/v2/authors?page={}", page - 1);
+	let data = auth::get_data(&url);
+	serde_json::from_slice(&data)
+		.map(|v: PageWrapperDto<AuthorDto>| {
+			let mut names: Vec<String> = Vec::new();
+			for author in v.content {
+				// authors are listed once per role
+				if !names.contains(&author.name) {
+					names.push(author.name);
+				}
+			}
+			MangaPageResult {
+				manga: names
+					.into_iter()
+					.map(|name| get_value_manga("Authors", "author", name))
+					.collect::<Vec<_>>(),
+				has_more: !v.last,
+			}
+		})
+		.map_err(|_| AidokuError {
+			reason: AidokuErrorKind::JsonParseError,
+		})
+}
+
+fn get_read_lists(base_url: &str, page: i32) -> Result<MangaPageResult> {
+	let url = format!("{base_url}/api/v1/readlists?page={}", page - 1);
+	let data = auth::get_data(&url);
+	serde_json::from_slice(&data)
+		.map(|v: PageWrapperDto<ReadListDto>| MangaPageResult {
+			manga: v
+				.content
+				.into_iter()
+				.map(|v| v.into_manga(base_url))
+				.collect::<Vec<_>>(),
+			has_more: !v.last,
+		})
+		.map_err(|_| AidokuError {
+			reason: AidokuErrorKind::JsonParseError,
+		})
+}
+
 #[get_manga_listing]
 fn get_manga_listing(listing: Listing, page: i32) -> Result<MangaPageResult> {
 	let base_url = get_base_url()?;
+	match listing.name.as_str() {
+		"Libraries" => return get_libraries(&base_url),
+		"Collections" => return get_collections(&base_url, page),
+		"Read Lists" => return get_read_lists(&base_url, page),
+		"Authors" => return get_authors(&base_url, page),
+		name => {
+			if let Some(result) = get_metadata_values(&base_url, name) {
+				return Ok(result);
+			}
+		}
+	}
 	let mut url = base_url.clone();
 	url.push_str(match listing.name.as_str() {
 		"Latest" => "/api/v1/series/latest",
@@ -121,9 +298,7 @@ fn get_manga_listing(listing: Listing, page: i32) -> Result<MangaPageResult> {
 	url.push_str("?deleted=false&page=");
 	url.push_str(itoa::Buffer::new().format(page - 1));
 
-	let data = Request::get(encode_uri(url))
-		.header("Authorization", &get_authorization_header())
-		.data();
+	let data = auth::get_data(&encode_uri(url));
 
 	serde_json::from_slice(&data)
 		.map(|v: PageWrapperDto<SeriesDto>| MangaPageResult {
@@ -139,13 +314,59 @@ fn get_manga_listing(listing: Listing, page: i32) -> Result<MangaPageResult> {
 		})
 }
 
+fn get_read_list_details(base_url: &str, id: &str) -> Result<Manga> {
+	let data = auth::get_data(&format!("{base_url}/api/v1/readlists/{id}"));
+	serde_json::from_slice(&data)
+		.map(|v: ReadListDto| v.into_manga(base_url))
+		.map_err(|_| AidokuError {
+			reason: AidokuErrorKind::JsonParseError,
+		})
+}
+
+/// Returns a library or collection as a manga, which has no chapters as
+/// its series are browsed through the search.
+fn get_group_details(base_url: &str, kind: &str, id: &str) -> Result<Manga> {
+	let manga = match kind {
+		"library" => {
+			let data = auth::get_data(&format!("{base_url}/api/v1/libraries/{id}"));
+			serde_json::from_slice(&data).map(|v: LibraryDto| v.into_manga(base_url))
+		}
+		_ => {
+			let data = auth::get_data(&format!("{base_url}/api/v1/collections/{id}"));
+			serde_json::from_slice(&data).map(|v: CollectionDto| v.into_manga(base_url))
+		}
+	};
+	manga.map_err(|_| AidokuError {
+		reason: AidokuErrorKind::JsonParseError,
+	})
+}
+
+/// Returns the books of a read list in its order, keeping their numbers.
+fn get_read_list_chapters(base_url: &str, id: &str) -> Result<Vec<Chapter>> {
+	let url = format!(
+		"{base_url}/api/v1/readlists/{id}/books?unpaged=true&media_status=READY&deleted=false"
+	);
+	get_books(base_url, url, true).map(|v| v.into_iter().rev().collect::<Vec<_>>())
+}
+
 #[get_manga_details]
 fn get_manga_details(id: String) -> Result<Manga> {
 	let base_url = get_base_url()?;
+	match id.split_once(':') {
+		Some(("readlist", id)) => return get_read_list_details(&base_url, id),
+		Some((kind @ ("library" | "collection"), id)) => {
+			return get_group_details(&base_url, kind, id)
+		}
+		Some(("author", value)) => return Ok(get_value_manga("Authors", "author", value.into())),
+		Some((kind, value)) => {
+			if let Some(filter) = METADATA_FILTERS.iter().find(|v| v.1 == kind) {
+				return Ok(get_value_manga(filter.0, filter.1, value.into()));
+			}
+		}
+		None => {}
+	}
 	let url = format!("{base_url}/api/v1/series/{id}");
-	let data = Request::get(encode_uri(url))
-		.header("Authorization", &get_authorization_header())
-		.data();
+	let data = auth::get_data(&encode_uri(url));
 	serde_json::from_slice(&data)
 		.map(|v: SeriesDto| v.into_manga(&base_url))
 		.map_err(|_| AidokuError {
@@ -156,71 +377,99 @@ fn get_manga_details(id: String) -> Result<Manga> {
 #[get_chapter_list]
 fn get_chapter_list(id: String) -> Result<Vec<Chapter>> {
 	let base_url = get_base_url()?;
+	match id.split_once(':') {
+		Some(("readlist", id)) => return get_read_list_chapters(&base_url, id),
+		Some(_) => return Ok(Vec::new()),
+		None => {}
+	}
 	let url = format!("{base_url}/api/v1/series/{id}/books?unpaged=true&media_status=READY&deleted=false");
-	let data = Request::get(encode_uri(url))
-		.header("Authorization", &get_authorization_header())
-		.data();
-	serde_json::from_slice(&data)
-		.map(|v: PageWrapperDto<BookDto>| {
-			v.content
-				.iter()
-				.map(|book| {
-					let mut date_updated = book
-						.metadata
-						.release_date
-						.as_ref()
-						.map(|v| StringRef::from(v).as_date("yyyy-MM-dd", Some("en_US"), None))
-						.unwrap_or(-1.0);
-					if date_updated == -1.0 {
-						date_updated = StringRef::from(&book.file_last_modified).as_date(
-							"yyyy-MM-dd'T'HH:mm:ss",
-							Some("en_US"),
-							None,
-						);
-					}
-					if date_updated == -1.0 {
-						date_updated = StringRef::from(&book.file_last_modified).as_date(
-							"yyyy-MM-dd'T'HH:mm:ss'Z",
-							Some("en_US"),
-							None,
-						);
-					}
-					if date_updated == -1.0 {
-						date_updated = StringRef::from(&book.file_last_modified).as_date(
-							"yyyy-MM-dd'T'HH:mm:ss.S",
-							Some("en_US"),
-							None,
-						);
-					}
-					Chapter {
-						id: book.id.to_owned(),
-						url: [&base_url, "/book/", book.id].concat(),
-						title: book.metadata.title.clone(),
-						chapter: book.metadata.number_sort,
-						date_updated,
-						..Default::default()
-					}
-				})
-				.rev()
-				.collect::<Vec<_>>()
-		})
-		.map_err(|_| AidokuError {
-			reason: AidokuErrorKind::JsonParseError,
-		})
+	get_books(&base_url, url, false).map(|v| v.into_iter().rev().collect::<Vec<_>>())
+}
+
+/// Returns the deep link of a book, within its read list when it was opened
+/// from one.
+fn get_book_deep_link(base_url: &str, id: &str, read_list_id: Option<&str>) -> Result<DeepLink> {
+	if let Some(read_list_id) = read_list_id {
+		let manga = get_read_list_details(base_url, read_list_id)?;
+		let chapter = get_read_list_chapters(base_url, read_list_id)?
+			.into_iter()
+			.find(|chapter| chapter.id == id);
+		return Ok(DeepLink {
+			manga: Some(manga),
+			chapter,
+		});
+	}
+
+	let data = auth::get_data(&format!("{base_url}/api/v1/books/{id}"));
+	let book = serde_json::from_slice::<BookDto>(&data).map_err(|_| AidokuError {
+		reason: AidokuErrorKind::JsonParseError,
+	})?;
+	let manga = get_manga_details(book.series_id.to_owned())?;
+	Ok(DeepLink {
+		manga: Some(manga),
+		chapter: Some(book.to_chapter(base_url)),
+	})
+}
+
+/// The error for urls that aren't a series, book or read list of the server.
+fn url_not_found() -> AidokuError {
+	// the closest kind to a link that can't be found
+	AidokuError {
+		reason: AidokuErrorKind::DefaultNotFound,
+	}
+}
+
+#[handle_url]
+fn handle_url(url: String) -> Result<DeepLink> {
+	let base_url = get_base_url()?;
+	let Some(path) = url.strip_prefix(&base_url) else {
+		return Err(url_not_found());
+	};
+	let (path, query) = path.split_once('?').unwrap_or((path, ""));
+	let parts = path
+		.split('/')
+		.filter(|v| !v.is_empty())
+		.collect::<Vec<_>>();
+	match parts.as_slice() {
+		["series", id, ..] => Ok(DeepLink {
+			manga: Some(get_manga_details(id.to_string())?),
+			chapter: None,
+		}),
+		// books read from a read list are opened as /book/{id}/read?context=readlist&contextId={id}
+		["book", id, ..] => {
+			let read_list_id = if query.contains("context=readlist") {
+				query.split('&').find_map(|v| v.strip_prefix("contextId="))
+			} else {
+				None
+			};
+			get_book_deep_link(&base_url, id, read_list_id)
+		}
+		["readlists", id, ..] => Ok(DeepLink {
+			manga: Some(get_read_list_details(&base_url, id)?),
+			chapter: None,
+		}),
+		_ => Err(url_not_found()),
+	}
 }
 
 #[modify_image_request]
 fn modify_image_request(request: Request) {
-	request.header("Authorization", &get_authorization_header());
+	auth::authorize_image(request);
+}
+
+#[handle_notification]
+fn handle_notification(notification: String) {
+	if notification == "authChanged" {
+		auth::clear_session();
+		search::clear_values();
+	}
 }
 
 #[get_page_list]
 fn get_page_list(_: String, id: String) -> Result<Vec<Page>> {
 	let base_url = get_base_url()?;
 	let url = format!("{base_url}/api/v1/books/{id}/pages");
-	let data = Request::get(encode_uri(&url))
-		.header("Authorization", &get_authorization_header())
-		.data();
+	let data = auth::get_data(&encode_uri(&url));
 	serde_json::from_slice(&data)
 		.map(|v: Vec<PageDto>| {
 			v.iter()
@@ -0,0 +1,270 @@
+use aidoku::{
+	helpers::uri::encode_uri_component,
+	prelude::*,
+	std::{String, Vec},
+};
+use alloc::{borrow::ToOwned, string::ToString, vec};
+use core::ptr;
+use serde_json::{json, Map, Value};
+
+use crate::{auth, dto::AuthorDto, dto::PageWrapperDto};
+
+/// Text filters holding comma separated metadata values, as (filter name,
+/// query parameter, search condition, endpoint listing the server's values).
+/// Values prefixed with `-` are excluded. The server's values are listed in
+/// the listing of the same name, and typed values are matched against them.
+pub const METADATA_FILTERS: [(&str, &str, &str, &str); 5] = [
+	("Genres", "genre", "genre", "/api/v1/genres"),
+	("Tags", "tag", "tag", "/api/v1/tags"),
+	("Publishers", "publisher", "publisher", "/api/v1/publishers"),
+	(
+		"Age Ratings",
+		"age_rating",
+		"ageRating",
+		"/api/v1/age-ratings",
+	),
+	("Languages", "language", "language", "/api/v1/languages"),
+];
+
+/// A series search, sent as query parameters to `/api/v1/series`, or as a
+/// search condition to `/api/v1/series/list` when values are excluded since
+/// only the latter (Komga 1.19 and later) supports it.
+#[derive(Default)]
+pub struct SeriesSearch {
+	params: String,
+	includes: Vec<(&'static str, Value)>,
+	excludes: Vec<Value>,
+	search: Option<String>,
+	sort: Option<String>,
+}
+
+/// The server's values of each metadata endpoint, fetched once per session.
+static mut VALUES: Vec<(String, Vec<String>)> = Vec::new();
+
+fn condition(field: &str, operator: &str, value: Option<Value>) -> Value {
+	let mut condition = Map::new();
+	condition.insert("operator".to_owned(), json!(operator));
+	if let Some(value) = value {
+		condition.insert("value".to_owned(), value);
+	}
+	json!({ field: condition })
+}
+
+/// Splits a comma separated filter into its included and excluded values.
+fn parse_values(text: &str) -> (Vec<&str>, Vec<&str>) {
+	let mut included = Vec::new();
+	let mut excluded = Vec::new();
+	for value in text.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()) {
+		match value.strip_prefix('-') {
+			Some(value) => excluded.push(value.trim()),
+			None => included.push(value),
+		}
+	}
+	(included, excluded)
+}
+
+/// Returns the server's spelling of `value`, as the search is exact.
+fn resolve_value(values: &[String], value: &str) -> String {
+	values
+		.iter()
+		.find(|v| v.eq_ignore_ascii_case(value))
+		.cloned()
+		.unwrap_or_else(|| value.to_owned())
+}
+
+/// Returns the server's values listed at `endpoint`.
+pub fn get_values(base_url: &str, endpoint: &str) -> Vec<String> {
+	let cache = unsafe { &mut *ptr::addr_of_mut!(VALUES) };
+	if let Some((_, values)) = cache.iter().find(|(e, _)| e == endpoint) {
+		return values.clone();
+	}
+	let data = auth::get_data(&format!("{base_url}{endpoint}"));
+	let values: Vec<String> = serde_json::from_slice(&data).unwrap_or_default();
+	cache.push((endpoint.to_owned(), values.clone()));
+	values
+}
+
+/// Forgets the server's values, for when the server or account changes.
+pub fn clear_values() {
+	unsafe { (*ptr::addr_of_mut!(VALUES)).clear() };
+}
+
+/// Returns the authors named `name`, with every role they have.
+fn get_authors(base_url: &str, name: &str) -> Vec<AuthorDto> {
+	let url = format!(
+		"{base_url}/api/v2/authors?unpaged=true&search={}",
+		encode_uri_component(name)
+	);
+	let data = auth::get_data(&url);
+	serde_json::from_slice::<PageWrapperDto<AuthorDto>>(&data)
+		.map(|v| {
+			v.content
+				.into_iter()
+				.filter(|author| author.name.eq_ignore_ascii_case(name))
+				.collect::<Vec<_>>()
+		})
+		.unwrap_or_default()
+}
+
+impl SeriesSearch {
+	fn push_param(&mut self, param: &str, value: &str) {
+		self.params.push('&');
+		self.params.push_str(param);
+		self.params.push('=');
+		self.params.push_str(&encode_uri_component(value));
+	}
+
+	/// Adds a raw `&param=value` filter id from `filters.json`.
+	pub fn push_filter_id(&mut self, id: &str) {
+		let Some((param, value)) = id.trim_start_matches('&').split_once('=') else {
+			return;
+		};
+		let field = match param {
+			"status" => "seriesStatus",
+			"read_status" => "readStatus",
+			_ => return,
+		};
+		self.include(param, value, field, Some(json!(value)));
+	}
+
+	/// Adds a value the series must have one of, per field.
+	pub fn include(
+		&mut self,
+		param: &str,
+		value: &str,
+		field: &'static str,
+		condition_value: Option<Value>,
+	) {
+		self.push_param(param, value);
+		let operator = if condition_value.is_some() {
+			"is"
+		} else {
+			"isNull"
+		};
+		self.includes
+			.push((field, condition(field, operator, condition_value)));
+	}
+
+	/// Adds a value the series must not have.
+	pub fn exclude(&mut self, field: &str, condition_value: Option<Value>) {
+		let operator = if condition_value.is_some() {
+			"isNot"
+		} else {
+			"isNotNull"
+		};
+		self.excludes
+			.push(condition(field, operator, condition_value));
+	}
+
+	/// Adds the values of a metadata filter, written as the server does.
+	pub fn push_metadata(
+		&mut self,
+		base_url: &str,
+		filter: (&str, &str, &'static str, &str),
+		text: &str,
+	) {
+		let (_, param, field, endpoint) = filter;
+		let (included, excluded) = parse_values(text);
+		if included.is_empty() && excluded.is_empty() {
+			return;
+		}
+		let values = get_values(base_url, endpoint);
+		let to_value = |value: &str| -> Option<Value> {
+			if field == "ageRating" {
+				value.parse::<i32>().ok().map(|v| json!(v))
+			} else {
+				Some(json!(value))
+			}
+		};
+		for value in included {
+			let value = resolve_value(&values, value);
+			self.include(param, &value, field, to_value(&value));
+		}
+		for value in excluded {
+			let value = resolve_value(&values, value);
+			self.exclude(field, to_value(&value));
+		}
+	}
+
+	/// Adds authors, with each of the roles they have on the server.
+	pub fn push_authors(&mut self, base_url: &str, text: &str) {
+		let (included, excluded) = parse_values(text);
+		for name in included {
+			for author in get_authors(base_url, name) {
+				self.push_param("author", &format!("{},{}", author.name, author.role));
+				self.includes.push((
+					"author",
+					condition(
+						"author",
+						"is",
+						Some(json!({ "name": author.name, "role": author.role })),
+					),
+				));
+			}
+		}
+		for name in excluded {
+			self.exclude("author", Some(json!({ "name": name })));
+		}
+	}
+
+	pub fn search(&mut self, title: &str) {
+		self.push_param("search", title);
+		self.search = Some(title.to_owned());
+	}
+
+	/// Searches with a regex, which only the query parameters support, so
+	/// it's left out when values are excluded.
+	pub fn search_regex(&mut self, regex: &str) {
+		self.push_param("search_regex", regex);
+	}
+
+	pub fn sort(&mut self, sort: String) {
+		self.push_param("sort", &sort);
+		self.sort = Some(sort);
+	}
+
+	fn body(&self) -> Value {
+		let mut conditions = vec![condition("deleted", "isFalse", None)];
+		let mut fields: Vec<&str> = Vec::new();
+		for (field, _) in &self.includes {
+			if !fields.contains(field) {
+				fields.push(field);
+			}
+		}
+		for field in fields {
+			let any_of = self
+				.includes
+				.iter()
+				.filter(|(f, _)| *f == field)
+				.map(|(_, condition)| condition.clone())
+				.collect::<Vec<_>>();
+			conditions.push(json!({ "anyOf": any_of }));
+		}
+		conditions.extend(self.excludes.iter().cloned());
+
+		let mut body = json!({ "condition": { "allOf": conditions } });
+		if let Some(search) = &self.search {
+			body["fullTextSearch"] = json!(search);
+		}
+		body
+	}
+
+	/// Sends the search, returning the page of series.
+	pub fn send(&self, base_url: &str, page: i32) -> Vec<u8> {
+		let page = itoa::Buffer::new().format(page - 1).to_string();
+		if self.excludes.is_empty() {
+			auth::get_data(&format!(
+				"{base_url}/api/v1/series?deleted=false&page={page}{}",
+				self.params
+			))
+		} else {
+			let mut url = format!("{base_url}/api/v1/series/list?page={page}");
+			if let Some(sort) = &self.sort {
+				url.push_str("&sort=");
+				url.push_str(&encode_uri_component(sort));
+			}
+			let body = serde_json::to_vec(&self.body()).unwrap_or_default();
+			auth::post_json(&url, &body)
+		}
+	}
+}
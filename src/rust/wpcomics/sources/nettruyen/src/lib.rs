@@ -2,12 +2,8 @@
 pub mod helper;
 use crate::helper::*;
 use aidoku::{
-	error::Result,
-	prelude::*,
-	std::net::Request,
-	std::String,
-	std::{defaults::defaults_get, Vec},
-	Chapter, DeepLink, Filter, FilterType, Listing, Manga, MangaPageResult, MangaViewer, Page,
+	error::Result, prelude::*, std::net::Request, std::String, std::Vec, Chapter, DeepLink, Filter,
+	FilterType, Listing, Manga, MangaPageResult, MangaViewer, Page,
 };
 use wpcomics_template::{helper::urlencode, template, template::WPComicsSource};
 
@@ -26,19 +22,7 @@ fn get_instance() -> WPComicsSource {
 		},
 		status_mapping: status_map,
 		time_converter: convert_time,
-		page_url_transformer: |url| {
-			let mut server_two = String::from("https://images2-focus-opensocial.googleusercontent.com/gadgets/proxy?container=focus&gadget=a&no_expand=1&resize_h=0&rewriteMime=image%2F*&url=");
-			if let Ok(server_selection) = defaults_get("serverSelection") {
-				if let Ok(2) = server_selection.as_int() {
-					server_two.push_str(&urlencode(url));
-					server_two
-				} else {
-					url
-				}
-			} else {
-				url
-			}
-		},
+		image_server_proxy: Some("https://images2-focus-opensocial.googleusercontent.com/gadgets/proxy?container=focus&gadget=a&no_expand=1&resize_h=0&rewriteMime=image%2F*&url="),
 		..Default::default()
 	}
 }
@@ -2,7 +2,7 @@
 use aidoku::{
 	error::Result,
 	prelude::*,
-	std::{defaults::defaults_get, net::Request, String, StringRef, Vec},
+	std::{net::Request, String, StringRef, Vec},
 	Chapter, DeepLink, Filter, FilterType, Listing, Manga, MangaPageResult, MangaStatus,
 	MangaViewer, Page,
 };
@@ -57,20 +57,7 @@ fn get_instance() -> WPComicsSource {
 		chapter_anchor_selector: "div.name-chap a",
 		chapter_date_selector: "div.time-chap",
 
-		page_url_transformer: |url| {
-			let mut server_two = String::from("https://images2-focus-opensocial.googleusercontent.com/gadgets/proxy?container=focus&gadget=a&no_expand=1&resize_h=0&rewriteMime=image%2F*&url=");
-			if let Ok(server_selection) = defaults_get("serverSelection") {
-				if let Ok(2) = server_selection.as_int() {
-					server_two.push_str(&urlencode(url));
-					server_two
-				} else {
-					url
-				}
-			} else {
-				url
-			}
-		},
-		vinahost_protection: true,
+		image_server_proxy: Some("https://images2-focus-opensocial.googleusercontent.com/gadgets/proxy?container=focus&gadget=a&no_expand=1&resize_h=0&rewriteMime=image%2F*&url="),
 		user_agent: Some(USER_AGENT),
 		..Default::default()
 	}
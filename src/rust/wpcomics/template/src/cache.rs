@@ -0,0 +1,52 @@
+use aidoku::std::{current_date, String, Vec};
+use core::ptr;
+
+/// Number of pages kept in the cache.
+const CACHE_CAPACITY: usize = 8;
+/// Seconds after which a cached page is requested again.
+const CACHE_TTL: f64 = 300.0;
+
+struct CacheEntry {
+	url: String,
+	data: Vec<u8>,
+	fetched_at: f64,
+}
+
+/// Least recently used pages, the most recently used one being last.
+static mut PAGE_CACHE: Vec<CacheEntry> = Vec::new();
+
+fn page_cache() -> &'static mut Vec<CacheEntry> {
+	unsafe { &mut *ptr::addr_of_mut!(PAGE_CACHE) }
+}
+
+/// Returns the cached response for `url`, or requests it with `fetch` and
+/// caches it, evicting the least recently used page when the cache is full.
+pub fn get_or_fetch<E, F: FnOnce() -> Result<Vec<u8>, E>>(
+	url: &str,
+	fetch: F,
+) -> Result<Vec<u8>, E> {
+	let cache = page_cache();
+	let now = current_date();
+	cache.retain(|entry| now - entry.fetched_at < CACHE_TTL);
+
+	if let Some(index) = cache.iter().position(|entry| entry.url == url) {
+		let entry = cache.remove(index);
+		let data = entry.data.clone();
+		cache.push(entry);
+		return Ok(data);
+	}
+
+	let data = fetch()?;
+	// don't remember failed requests
+	if !data.is_empty() {
+		if cache.len() >= CACHE_CAPACITY {
+			cache.remove(0);
+		}
+		cache.push(CacheEntry {
+			url: String::from(url),
+			data: data.clone(),
+			fetched_at: now,
+		});
+	}
+	Ok(data)
+}
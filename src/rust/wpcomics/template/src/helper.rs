@@ -1,5 +1,6 @@
 use aidoku::{
-	prelude::format, std::html::Node, std::String, std::Vec, MangaContentRating, MangaViewer,
+	prelude::format, std::defaults::defaults_get, std::html::Node, std::String, std::Vec,
+	MangaContentRating, MangaViewer,
 };
 
 pub fn trunc_trailing_comic(title: String) -> String {
@@ -36,6 +37,17 @@ pub fn append_protocol(url: String) -> String {
 	}
 }
 
+/// Returns the server picked in the `serverSelection` setting, 1 by default.
+pub fn get_server_selection() -> i64 {
+	match defaults_get("serverSelection") {
+		Ok(value) => value
+			.as_int()
+			.or_else(|_| value.as_string().map(|v| v.read().parse().unwrap_or(1)))
+			.unwrap_or(1),
+		Err(_) => 1,
+	}
+}
+
 pub fn urlencode(string: String) -> String {
 	let mut result: Vec<u8> = Vec::with_capacity(string.len() * 3);
 	let hex = "0123456789abcdef".as_bytes();
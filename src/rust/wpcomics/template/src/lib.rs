@@ -1,3 +1,4 @@
 #![no_std]
+pub mod cache;
 pub mod helper;
 pub mod template;
@@ -1,10 +1,15 @@
 use aidoku::{
-	error::Result, prelude::*, std::html::Node, std::net::HttpMethod, std::net::Request,
-	std::String, std::StringRef, std::Vec, Chapter, DeepLink, Listing, Manga, MangaContentRating,
-	MangaPageResult, MangaStatus, MangaViewer, Page,
+	error::Result, prelude::*, std::current_date, std::html::Node, std::net::HttpMethod,
+	std::net::Request, std::String, std::StringRef, std::Vec, Chapter, DeepLink, Listing, Manga,
+	MangaContentRating, MangaPageResult, MangaStatus, MangaViewer, Page,
 };
 
-use crate::helper::{append_protocol, extract_f32_from_string, text_with_newlines};
+use crate::cache::get_or_fetch;
+use crate::helper::{
+	append_protocol, extract_f32_from_string, get_server_selection, text_with_newlines, urlencode,
+};
+use challenge::{ChallengeError, ChallengeRequest};
+use core::ptr;
 
 pub struct WPComicsSource {
 	pub base_url: String,
@@ -43,82 +48,50 @@ pub struct WPComicsSource {
 
 	pub manga_viewer_page: &'static str,
 	pub manga_viewer_page_url_suffix: &'static str,
+	/// Attributes of `manga_viewer_page` holding an image url, the first one
+	/// being the primary server and the others its backups
+	pub manga_viewer_page_attrs: &'static [&'static str],
+	/// Image proxy prefix used by the second server of the `serverSelection`
+	/// setting, the image url is appended to it url encoded
+	pub image_server_proxy: Option<&'static str>,
 	pub page_url_transformer: fn(String) -> String,
 
-	pub vinahost_protection: bool,
-
 	pub user_agent: Option<&'static str>,
 }
 
-static mut CACHED_MANGA_ID: Option<String> = None;
-static mut CACHED_MANGA: Option<Vec<u8>> = None;
-static mut VINAHOST_COOKIE: Option<String> = None;
+/// Seconds after which an image host is probed again
+const IMAGE_HOST_PROBE_TTL: f64 = 10.0 * 60.0;
 
-fn cache_manga_page(data: &WPComicsSource, url: &str) {
-	if unsafe { CACHED_MANGA_ID.is_some() } && unsafe { CACHED_MANGA_ID.clone().unwrap() } == url {
-		return;
-	}
+/// Image hosts probed so far, whether they worked and when they were probed
+static mut PROBED_IMAGE_HOSTS: Vec<(String, bool, f64)> = Vec::new();
 
-	unsafe {
-		if data.vinahost_protection {
-			CACHED_MANGA = Some(data.request_vinahost(url).data());
-		} else {
-			let mut req = Request::new(url, HttpMethod::Get);
-			if let Some(user_agent) = data.user_agent {
-				req = req.header("User-Agent", user_agent);
-			}
-			CACHED_MANGA = Some(req.data());
-		}
-		CACHED_MANGA_ID = Some(String::from(url));
-	};
+fn get_host(url: &str) -> &str {
+	url.split('/').nth(2).unwrap_or_default()
+}
+
+/// Returns the image hosts probed within `IMAGE_HOST_PROBE_TTL`
+fn probed_image_hosts() -> &'static mut Vec<(String, bool, f64)> {
+	let hosts = unsafe { &mut *ptr::addr_of_mut!(PROBED_IMAGE_HOSTS) };
+	let now = current_date();
+	hosts.retain(|(_, _, probed_at)| now - probed_at < IMAGE_HOST_PROBE_TTL);
+	hosts
 }
 
 impl WPComicsSource {
-	fn request_vinahost(&self, url: &str) -> Request {
-		if unsafe { VINAHOST_COOKIE.is_some() } {
-			let mut req = Request::new(url, HttpMethod::Get).header(
-				"Cookie",
-				unsafe { VINAHOST_COOKIE.clone().unwrap() }.as_str(),
-			);
-			if let Some(user_agent) = self.user_agent {
-				req = req.header("User-Agent", user_agent);
-			}
-			req
-		} else if self.vinahost_protection {
-			let mut req = Request::new(url, HttpMethod::Get);
-			if let Some(user_agent) = self.user_agent {
-				req = req.header("User-Agent", user_agent);
-			}
-			if let Ok(blocked_html) = req.html() {
-				let script = blocked_html.select("script").html().read();
-				let cookie = script
-					.replace("document.cookie=\"", "")
-					.replace("\";window.location.reload(true);", "")
-					.replace("\"+\"", "");
-				unsafe {
-					VINAHOST_COOKIE = Some(cookie);
-				};
-				let mut req = Request::new(url, HttpMethod::Get).header(
-					"Cookie",
-					unsafe { VINAHOST_COOKIE.clone().unwrap() }.as_str(),
-				);
-				if let Some(user_agent) = self.user_agent {
-					req = req.header("User-Agent", user_agent);
-				}
-				req
-			} else {
-				let mut req = Request::new(url, HttpMethod::Get);
-				if let Some(user_agent) = self.user_agent {
-					req = req.header("User-Agent", user_agent);
-				}
-				req
-			}
-		} else {
-			let mut req = Request::new(url, HttpMethod::Get);
-			if let Some(user_agent) = self.user_agent {
-				req = req.header("User-Agent", user_agent);
-			}
-			req
+	/// Parses the page at `url`, which is only requested again once it has
+	/// left the page cache.
+	fn get_cached_html(&self, url: &str) -> Result<Node> {
+		let data = get_or_fetch(url, || {
+			self.request(url).data().map_err(ChallengeError::report)
+		})?;
+		Node::new(&data)
+	}
+
+	/// Requests `url`, solving the anti-bot challenges served instead
+	fn request(&self, url: &str) -> ChallengeRequest<'_> {
+		match self.user_agent {
+			Some(user_agent) => ChallengeRequest::get(url).header("User-Agent", user_agent),
+			None => ChallengeRequest::get(url),
 		}
 	}
 
@@ -155,7 +128,10 @@ impl WPComicsSource {
 
 	pub fn get_manga_list(&self, search_url: String) -> Result<MangaPageResult> {
 		let mut has_next_page = !self.next_page.is_empty();
-		let html = self.request_vinahost(&search_url).html()?;
+		let html = self
+			.request(&search_url)
+			.html()
+			.map_err(ChallengeError::report)?;
 		let node = html.select(self.manga_cell);
 		let elems = node.array();
 		let mut mangas: Vec<Manga> = Vec::with_capacity(elems.len());
@@ -217,8 +193,7 @@ impl WPComicsSource {
 	}
 
 	pub fn get_manga_details(&self, id: String) -> Result<Manga> {
-		cache_manga_page(self, id.as_str());
-		let details = unsafe { Node::new(&CACHED_MANGA.clone().unwrap())? };
+		let details = self.get_cached_html(&id)?;
 		let title = details.select(self.manga_details_title).text().read();
 		let cover = append_protocol(details.select(self.manga_details_cover).attr("src").read());
 		let author = (self.manga_details_author_transformer)(
@@ -267,8 +242,7 @@ impl WPComicsSource {
 	pub fn get_chapter_list(&self, id: String) -> Result<Vec<Chapter>> {
 		let mut skipped_first = false;
 		let mut chapters: Vec<Chapter> = Vec::new();
-		cache_manga_page(self, id.as_str());
-		let html = unsafe { Node::new(&CACHED_MANGA.clone().unwrap())? };
+		let html = self.get_cached_html(&id)?;
 		let title_untrimmed = (self.manga_details_title_transformer)(
 			html.select(self.manga_details_title).text().read(),
 		);
@@ -343,19 +317,102 @@ impl WPComicsSource {
 		Ok(chapters)
 	}
 
+	/// Returns the image urls of a page, in the order of the servers they
+	/// are on, starting with the one picked in the `serverSelection` setting
+	fn get_page_urls(&self, page_node: &Node) -> Vec<String> {
+		let mut urls: Vec<String> = Vec::new();
+		for attr in self.manga_viewer_page_attrs {
+			let url = page_node.attr(attr).read();
+			let url = String::from(url.trim());
+			if url.is_empty() || url.starts_with("data:") {
+				continue;
+			}
+			let url = append_protocol(url);
+			if !urls.contains(&url) {
+				urls.push(url);
+			}
+		}
+
+		if let Some(proxy) = self.image_server_proxy {
+			let proxied = urls
+				.iter()
+				.map(|url| format!("{}{}", proxy, urlencode(url.clone())))
+				.collect::<Vec<_>>();
+			if get_server_selection() == 2 {
+				urls = [proxied, urls].concat();
+			} else {
+				urls.extend(proxied);
+			}
+		}
+
+		urls.into_iter().map(self.page_url_transformer).collect()
+	}
+
+	/// Requests the first byte of an image the way the reader would, to know
+	/// if its host works
+	fn is_image_available(&self, url: &str) -> bool {
+		let mut request = Request::new(url, HttpMethod::Get)
+			.header("Referer", &format!("{}/", self.base_url))
+			.header("Range", "bytes=0-0");
+		if let Some(user_agent) = self.user_agent {
+			request = request.header("User-Agent", user_agent);
+		}
+		request.send();
+		(200..300).contains(&request.status_code())
+	}
+
+	/// Picks the url of a page on the first server whose host works. The
+	/// last server isn't probed as there's nothing to fall back to, and if
+	/// every host failed the one that did the longest ago is used.
+	fn select_page_url(&self, urls: &[String]) -> Option<String> {
+		for (at, url) in urls.iter().enumerate() {
+			let host = get_host(url);
+			let hosts = probed_image_hosts();
+			let available = match hosts.iter().find(|(probed, _, _)| probed == host) {
+				Some((_, available, _)) => *available,
+				None if at + 1 == urls.len() => true,
+				None => {
+					let available = self.is_image_available(url);
+					hosts.push((String::from(host), available, current_date()));
+					available
+				}
+			};
+			if available {
+				return Some(url.clone());
+			}
+		}
+
+		let hosts = probed_image_hosts();
+		let probed_at = |url: &String| {
+			hosts
+				.iter()
+				.find(|(probed, _, _)| probed == get_host(url))
+				.map(|(_, _, probed_at)| *probed_at)
+				.unwrap_or(0.0)
+		};
+		urls.iter()
+			.min_by(|a, b| probed_at(a).total_cmp(&probed_at(b)))
+			.cloned()
+	}
+
 	pub fn get_page_list(&self, chapter_id: String) -> Result<Vec<Page>> {
-		let mut pages: Vec<Page> = Vec::new();
 		let url = format!("{}{}", &chapter_id, self.manga_viewer_page_url_suffix);
-		let html = self.request_vinahost(&url).html()?;
-		for (at, page) in html.select(self.manga_viewer_page).array().enumerate() {
-			let page_node = page.as_node().expect("node array");
-			let mut page_url = page_node.attr("data-original").read();
-			if !page_url.starts_with("http") {
-				page_url = String::from("https:") + &page_url;
-			}
+		let html = self.get_cached_html(&url)?;
+		let page_urls = html
+			.select(self.manga_viewer_page)
+			.array()
+			.filter_map(|page| page.as_node().ok())
+			.map(|page_node| self.get_page_urls(&page_node))
+			.collect::<Vec<_>>();
+
+		let mut pages: Vec<Page> = Vec::new();
+		for urls in page_urls {
+			let Some(url) = self.select_page_url(&urls) else {
+				continue;
+			};
 			pages.push(Page {
-				index: at as i32,
-				url: (self.page_url_transformer)(page_url),
+				index: pages.len() as i32,
+				url,
 				base64: String::new(),
 				text: String::new(),
 			});
@@ -364,8 +421,7 @@ impl WPComicsSource {
 	}
 
 	pub fn handle_url(&self, url: String) -> Result<DeepLink> {
-		cache_manga_page(self, url.as_str());
-		let html = unsafe { Node::new(CACHED_MANGA.clone().unwrap())? };
+		let html = self.get_cached_html(&url)?;
 		if html.select(self.manga_viewer_page).array().is_empty() {
 			let node = html.select(".breadcrumb li");
 			let breadcrumbs = node.array();
@@ -400,6 +456,8 @@ impl WPComicsSource {
 		}
 	}
 
+	/// The url of an image request can't be read here, so the backup servers
+	/// are picked by `get_page_list` instead
 	pub fn modify_image_request(&self, mut request: Request) {
 		if let Some(user_agent) = self.user_agent {
 			request = request.header("User-Agent", user_agent);
@@ -457,10 +515,10 @@ impl Default for WPComicsSource {
 
 			manga_viewer_page: "div.page-chapter > img",
 			manga_viewer_page_url_suffix: "",
+			manga_viewer_page_attrs: &["data-original", "data-cdn"],
+			image_server_proxy: None,
 			page_url_transformer: |url| url,
 
-			vinahost_protection: false,
-
 			user_agent: None,
 		}
 	}